@@ -1,5 +1,5 @@
-use std::{io, result};
-use std::collections::{BTreeSet, BTreeMap, VecDeque};
+use std::io;
+use std::collections::{BTreeSet, BTreeMap};
 use std::ops::Deref;
 
 
@@ -89,10 +89,10 @@ fn calculate_nth_destroy_asteroid(center: Vec2i, others: &[Vec2i], nth: usize) -
         for item in targets_vec.iter_mut() {
             results.push(item.1.pop().unwrap());
         }
-        targets_vec = targets_vec.into_iter().filter(|x| !x.1.is_empty()).collect();
+        targets_vec.retain(|x| !x.1.is_empty());
     }
 
-    return results[nth] + center;
+    results[nth] + center
 }
 
 fn parse_asteroids_map<S: Deref<Target = str>, T: Iterator<Item = S>>(input_map: T) -> Vec<Vec2i> {
@@ -104,7 +104,7 @@ fn parse_asteroids_map<S: Deref<Target = str>, T: Iterator<Item = S>>(input_map:
             }
         }
     }
-    return result;
+    result
 }
 
 fn solve_a(map: &[Vec2i]) -> i32 {
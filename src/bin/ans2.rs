@@ -1,71 +1,57 @@
 use std::io;
+use adv2019::intcode::computer::{ExecState, IntcodeComputer};
+use adv2019::intcode::fingerprint::{self, Family};
+use adv2019::intcode::io::{BufferInput, BufferOutput};
+use adv2019::intcode::isa::IsaProfile;
+use adv2019::intcode::program::Program;
+use adv2019::intcode::sweep;
 
-struct Machine {
-    mem: Vec<i64>,
-    cursor: usize,
-}
-
-impl Machine {
-    fn _value_ref(&mut self, offset: i32) -> &mut i64 {
-        let pos = self.mem[self.cursor + offset as usize];
-        &mut self.mem[pos as usize]
-    }
-    // return true if should stop
-    fn run_one_step(&mut self) -> bool {
-        let skips = match self.mem[self.cursor] {
-            1 => {
-                *self._value_ref(3) = *self._value_ref(1) + *self._value_ref(2);
-                4
-            },
-            2 => {
-                *self._value_ref(3) = *self._value_ref(1) * *self._value_ref(2);
-                4
-            },
-            _ => 0,
-        };
-        self.cursor += skips;
-        return skips == 0;
-    }
-    pub fn run(&mut self) -> i64 {
-        while !self.run_one_step() {}
-        self.mem[0]
-    }
+fn run_machine(mem: Vec<i64>) -> i64 {
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(&[]), BufferOutput::default());
+    computer.set_profile(IsaProfile::DAY2);
+    assert_eq!(computer.run_until_blocked(), ExecState::Halted);
+    computer.peek(0)
 }
 
 #[test]
 fn test_machine() {
-    let mut machine = Machine {
-        cursor: 0,
-        mem: vec![1,9,10,3,2,3,11,0,99,30,40,50],
-    };
-    let res = machine.run();
-    assert_eq!(res, 3500);
+    assert_eq!(run_machine(vec![1,9,10,3,2,3,11,0,99,30,40,50]), 3500);
 }
 
-fn run_machine_with_noun_verb(orig_mem: &Vec<i64>, noun: i64, verb: i64) -> i64 {
-    let mut mem = orig_mem.clone();
+fn run_machine_with_noun_verb(orig_mem: &[i64], noun: i64, verb: i64) -> i64 {
+    let mut mem = orig_mem.to_vec();
     mem[1] = noun;
     mem[2] = verb;
-    let mut machine = Machine {
-        cursor: 0,
-        mem,
-    };
-    machine.run()
+    run_machine(mem)
+}
+
+fn find_noun_verb(mem: &[i64], target: i64) -> Option<(i64, i64)> {
+    let candidates = sweep::patch_grid(&[1, 2], &(0..100).collect::<Vec<i64>>());
+    let options = sweep::Options { max_steps: 10_000, profile: Some(IsaProfile::DAY2) };
+    let halted = sweep::Verdict::Stopped(ExecState::Halted);
+    sweep::find_first(mem, &candidates, &options, |outcome| outcome.verdict == halted && outcome.mem(0) == target)
+        .map(|outcome| (outcome.mem(1), outcome.mem(2)))
 }
 
 fn main() {
     let mut line = String::new();
     io::stdin().read_line(&mut line).unwrap();
-    let mem: Vec<i64> = line.trim_end().split(",").map(|x| x.parse().unwrap()).collect();
+    let mem = Program::parse(&line).unwrap().words;
+    if let Err(err) = fingerprint::check(&mem, Family::GravityAssist) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
     println!("1: {}", run_machine_with_noun_verb(&mem, 12, 2));
 
-    for noun in 0i64..100 {
-        for verb in 0i64..100 {
-            if run_machine_with_noun_verb(&mem, noun, verb) == 19690720 {
-                println!("2: {}, {}: {}", noun, verb, noun * 100 + verb);
-                break;
-            }
-        }
+    if let Some((noun, verb)) = find_noun_verb(&mem, 19690720) {
+        println!("2: {}, {}: {}", noun, verb, noun * 100 + verb);
     }
 }
+
+#[test]
+fn test_final() {
+    let mem = Program::parse(include_str!("../../input/2")).unwrap().words;
+    assert_eq!(run_machine_with_noun_verb(&mem, 12, 2), 4138658);
+    assert_eq!(find_noun_verb(&mem, 19690720), Some((72, 64)));
+}
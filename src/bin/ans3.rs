@@ -14,17 +14,12 @@ type Coord = (i32, i32);
 
 type PointInfo = HashMap<usize, i32>;  // path id -> step
 
+#[derive(Default)]
 struct Grid {
     grid: HashMap<Coord, PointInfo>,
     next_path_id: usize,
 }
 
-impl Default for Grid {
-    fn default() -> Self {
-        Grid { grid: HashMap::new(), next_path_id: 0 }
-    }
-}
-
 impl Grid {
     fn find_closest_intersect_distance(&self) -> i32 {
         self.grid.iter()
@@ -69,7 +64,7 @@ impl Grid {
 fn parse_motions(line: &str) -> Vec<Motion> {
     line.trim_end().split(",")
         .map(|s| (
-            match s.chars().nth(0).unwrap() {
+            match s.chars().next().unwrap() {
                 'R' => Direction::Right,
                 'L' => Direction::Left,
                 'U' => Direction::Up,
@@ -6,13 +6,12 @@ fn is_valid(num: i32, strict_single_dup: bool) -> bool {
         if digits[i] > digits[i+1] {
             return false;
         }
-        if digits[i] == digits[i+1] {
-            if !strict_single_dup || ((i == 0 || digits[i-1] != digits[i]) && (i == 4 || digits[i+2] != digits[i+1])) {
-                has_dup = true;
-            }
+        if digits[i] == digits[i+1]
+            && (!strict_single_dup || ((i == 0 || digits[i-1] != digits[i]) && (i == 4 || digits[i+2] != digits[i+1]))) {
+            has_dup = true;
         }
     }
-    return has_dup;
+    has_dup
 }
 
 #[test]
@@ -1,16 +1,17 @@
 use std::{io, ops::Deref};
 use adv2019::intcode;
+use adv2019::intcode::fingerprint::{self, Family};
+use adv2019::intcode::program::Program;
 
 fn run_with_input(prog: &str, input: i64) -> i64 {
-    let mem: Vec<i64> = prog.trim_end().split(",").map(|x| x.parse().unwrap()).collect();
     let mut computer = intcode::computer::IntcodeComputer::new(
-        mem, intcode::io::BufferInput::new(&[input]), intcode::io::BufferOutput::default(),
+        Program::parse(prog).unwrap().words, intcode::io::BufferInput::new(&[input]), intcode::io::BufferOutput::default(),
     );
     computer.run_until_finish();
 
     let outputs: &[i64] = computer.output_ref().deref();
-    for i in 0..(outputs.len()-1) {
-        assert_eq!(outputs[i], 0);
+    for output in &outputs[..outputs.len()-1] {
+        assert_eq!(*output, 0);
     }
     *outputs.last().unwrap()
 }
@@ -18,6 +19,10 @@ fn run_with_input(prog: &str, input: i64) -> i64 {
 fn main() {
     let mut line = String::new();
     io::stdin().read_line(&mut line).unwrap();
+    if let Err(err) = fingerprint::check(&Program::parse(&line).unwrap().words, Family::Diagnostic) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
     println!("{}", run_with_input(&line, 1));
     println!("{}", run_with_input(&line, 5));
@@ -39,7 +39,7 @@ impl World {
     fn find_all_roots(&self) -> Vec<Rc<RefCell<Node>>> {
         self.nodes.values()
             .filter(|node| node.borrow().is_root)
-            .map(|x| x.clone())
+            .cloned()
             .collect()
     }
 
@@ -65,7 +65,7 @@ impl World {
                 return next_path;
             }
         }
-        return Vec::new()
+        Vec::new()
     }
 
     fn compute_distance(&self, src: &str, dst: &str) -> i32 {
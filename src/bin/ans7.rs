@@ -1,85 +1,25 @@
-use std::cell::RefCell;
 use std::io;
-use std::ops::Deref;
-use std::sync::mpsc;
-use adv2019::intcode;
+use adv2019::intcode::fingerprint::{self, Family};
+use adv2019::intcode::program::Program;
+use adv2019::intcode::topology::Topology;
 
-struct ChannelInput {
-    receiver: mpsc::Receiver<i64>,
-}
-
-impl intcode::io::Input for ChannelInput {
-    fn read(&mut self) -> Option<i64> {
-        self.receiver.recv().ok()
-    }
-}
+const AMPLIFIERS: [&str; 5] = ["A", "B", "C", "D", "E"];
 
-struct ChannelOutput {
-    sender: mpsc::Sender<i64>,
-    extra_sender: Option<mpsc::Sender<i64>>,
-}
-
-impl intcode::io::Output for ChannelOutput {
-    fn write(&mut self, val: i64) {
-        let _ = self.sender.send(val); // it's ok to fail here
-        if let Some(extra_sender) = &self.extra_sender {
-            extra_sender.send(val).unwrap();
-        }
+fn run_amplifiers(mut topology: Topology, phase_settings: &[usize; 5]) -> i64 {
+    for (name, phase) in AMPLIFIERS.iter().zip(phase_settings) {
+        topology.initial_input(name, &[*phase as i64]);
     }
+    topology.initial_input("A", &[0]);
+    topology.collect("E");
+    *topology.run().unwrap().collected("E").last().unwrap()
 }
 
 fn run_amplifier_chain(mem: Vec<i64>, phase_settings: &[usize; 5]) -> i64 {
-    let mut last_output = 0;
-    for i in 0..5 {
-        let mut computer = intcode::computer::IntcodeComputer::new(
-            mem.clone(),
-            intcode::io::BufferInput::new(&[phase_settings[i] as i64, last_output]),
-            intcode::io::BufferOutput::default()
-        );
-        computer.run_until_finish();
-        last_output = *computer.output_ref().deref().last().unwrap();
-    }
-    last_output
+    run_amplifiers(Topology::chain(&AMPLIFIERS, &mem), phase_settings)
 }
 
 fn run_amplifier_loop(mem: Vec<i64>, phase_settings: &[usize; 5]) -> i64 {
-    // the last one is for output only
-    let channels: [(RefCell<Option<mpsc::Sender<i64>>>, RefCell<Option<mpsc::Receiver<i64>>>); 6] =
-        core::array::from_fn(|_| {
-            let (sender, receiver) = mpsc::channel();
-            (RefCell::new(Some(sender)), RefCell::new(Some(receiver)))
-        });
-
-    for i in 0..5 {
-        channels[i].0.borrow().as_ref().unwrap().send(phase_settings[i] as i64).unwrap();
-    }
-    channels[0].0.borrow().as_ref().unwrap().send(0).unwrap();
-
-    let computers: [intcode::computer::IntcodeComputer<ChannelInput, ChannelOutput>; 5] =
-        core::array::from_fn(|i| {
-            intcode::computer::IntcodeComputer::new(
-                mem.clone(),
-                ChannelInput { receiver: channels[i].1.take().unwrap(), },
-                ChannelOutput {
-                    sender: channels[(i+1)%5].0.take().unwrap(),
-                    extra_sender: if i == 4 { Some(channels[5].0.take().unwrap()) } else { None }
-                },
-            )
-        });
-
-    let threads: Vec<std::thread::JoinHandle<_>> = computers.into_iter()
-        .map(|mut computer| { std::thread::spawn(move || computer.run_until_finish()) })
-        .collect();
-    for th in threads {
-        th.join().unwrap();
-    }
-
-    let final_receiver: mpsc::Receiver<i64> = channels[5].1.take().unwrap();
-    let mut result = 0_i64;
-    while let Ok(val) = final_receiver.recv() {
-        result = val;
-    }
-    return result;
+    run_amplifiers(Topology::ring(&AMPLIFIERS, &mem), phase_settings)
 }
 
 fn generate_permutations<const N: usize>(nums: &[usize; N]) -> Vec<[usize; N]> {
@@ -87,7 +27,7 @@ fn generate_permutations<const N: usize>(nums: &[usize; N]) -> Vec<[usize; N]> {
                                          nums: &[usize; N], current: &mut [usize; N],
                                          result: &mut Vec<[usize; N]>) {
         if idx == N {
-            result.push(current.clone());
+            result.push(*current);
             return;
         }
         for i in 0..N {
@@ -108,12 +48,12 @@ fn test_permutations() {
 }
 
 fn solve(line: &str) -> (i64, i64) {
-    let mem: Vec<i64> = line.trim_end().split(",").map(|x| x.parse().unwrap()).collect();
+    let mem = Program::parse(line).unwrap().words;
     let ans_0 = generate_permutations::<5>(&[0,1,2,3,4]).iter()
-        .map(|phase_settings| run_amplifier_chain(mem.clone(), &phase_settings))
+        .map(|phase_settings| run_amplifier_chain(mem.clone(), phase_settings))
         .max();
     let ans_1 = generate_permutations::<5>(&[5,6,7,8,9]).iter()
-        .map(|phase_settings| run_amplifier_loop(mem.clone(), &phase_settings))
+        .map(|phase_settings| run_amplifier_loop(mem.clone(), phase_settings))
         .max();
 
     (ans_0.unwrap(), ans_1.unwrap())
@@ -123,6 +63,10 @@ fn solve(line: &str) -> (i64, i64) {
 fn main() {
     let mut line = String::new();
     io::stdin().read_line(&mut line).unwrap();
+    if let Err(err) = fingerprint::check(&Program::parse(&line).unwrap().words, Family::Amplifier) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
     println!("{:?}", solve(&line));
 }
@@ -13,11 +13,11 @@ fn solve_b(content: &str, width: usize, height: usize) {
     for y in 0..height {
         for x in 0..width {
             let res = content.chars()
-                .skip(y*width+x).step_by(width*height).skip_while(|c| *c == '2')
-                .next().unwrap_or('x');
+                .skip(y*width+x).step_by(width*height).find(|c| *c != '2')
+                .unwrap_or('x');
             print!("{}", res);
         }
-        print!("\n");
+        println!();
     }
 }
 
@@ -2,11 +2,12 @@ use std::ops::Deref;
 use std::io;
 
 use adv2019::intcode;
+use adv2019::intcode::fingerprint::{self, Family};
+use adv2019::intcode::program::Program;
 
 fn run(prog: &str, inputs: &[i64]) -> Vec<i64> {
-    let mem: Vec<i64> = prog.trim_end().split(",").map(|x| x.parse().unwrap()).collect();
     let mut computer = intcode::computer::IntcodeComputer::new(
-        mem,
+        Program::parse(prog).unwrap().words,
         intcode::io::BufferInput::new(inputs),
         intcode::io::BufferOutput::default()
     );
@@ -25,6 +26,10 @@ fn test_example() {
 fn main() {
     let mut line = String::new();
     io::stdin().read_line(&mut line).unwrap();
+    if let Err(err) = fingerprint::check(&Program::parse(&line).unwrap().words, Family::Boost) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 
     println!("{:?}", run(&line, &[1_i64]));
     println!("{:?}", run(&line, &[2_i64]));
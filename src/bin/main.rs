@@ -1,5 +1,3 @@
-use adv2019::intcode::*;
-
 fn main() {
     println!("hello world!");
 }
@@ -1,3 +1,28 @@
-mod inst;
+pub mod inst;
 pub mod io;
 pub mod computer;
+pub mod decompile;
+pub mod device;
+pub mod difftest;
+pub mod engine;
+pub mod ext;
+pub mod fingerprint;
+pub mod image;
+pub mod isa;
+pub mod lint;
+pub mod network;
+pub mod observer;
+pub mod optimize;
+pub mod program;
+pub mod aio;
+pub mod executor;
+pub mod fuzz;
+pub mod gdb;
+pub mod scanner;
+pub mod scheduler;
+pub mod smc;
+pub mod socket;
+pub mod sweep;
+pub mod symbolic;
+pub mod taint;
+pub mod topology;
@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::{Operation, Word};
+use super::observer::Observer;
+
+pub trait AsyncInput {
+    // Ready(None) means the input is closed and will never produce more values
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<Word>>;
+}
+
+pub trait AsyncOutput {
+    fn poll_write(&mut self, cx: &mut Context<'_>, val: Word) -> Poll<()>;
+}
+
+
+struct ChannelState {
+    queue: VecDeque<Word>,
+    capacity: usize,
+    closed: bool,
+    reader: Option<Waker>,
+    writer: Option<Waker>,
+}
+
+pub struct AsyncSender {
+    state: Rc<RefCell<ChannelState>>,
+}
+
+pub struct AsyncReceiver {
+    state: Rc<RefCell<ChannelState>>,
+}
+
+// single-threaded bounded channel; a full queue suspends the writing machine
+pub fn channel(capacity: usize) -> (AsyncSender, AsyncReceiver) {
+    assert!(capacity > 0, "capacity must be positive");
+    let state = Rc::new(RefCell::new(ChannelState {
+        queue: VecDeque::new(),
+        capacity,
+        closed: false,
+        reader: None,
+        writer: None,
+    }));
+    (AsyncSender { state: state.clone() }, AsyncReceiver { state })
+}
+
+impl AsyncSender {
+    // queue a value regardless of the capacity, e.g. for initial phase settings
+    pub fn push(&self, val: Word) {
+        let mut state = self.state.borrow_mut();
+        state.queue.push_back(val);
+        if let Some(waker) = state.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl AsyncOutput for AsyncSender {
+    fn poll_write(&mut self, cx: &mut Context<'_>, val: Word) -> Poll<()> {
+        let mut state = self.state.borrow_mut();
+        if state.queue.len() >= state.capacity {
+            state.writer = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        state.queue.push_back(val);
+        if let Some(waker) = state.reader.take() {
+            waker.wake();
+        }
+        Poll::Ready(())
+    }
+}
+
+impl Drop for AsyncSender {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.closed = true;
+        if let Some(waker) = state.reader.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl AsyncInput for AsyncReceiver {
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<Word>> {
+        let mut state = self.state.borrow_mut();
+        match state.queue.pop_front() {
+            Some(v) => {
+                if let Some(waker) = state.writer.take() {
+                    waker.wake();
+                }
+                Poll::Ready(Some(v))
+            },
+            None if state.closed => Poll::Ready(None),
+            None => {
+                state.reader = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+}
+
+impl AsyncReceiver {
+    pub fn try_recv(&self) -> Option<Word> {
+        self.state.borrow_mut().queue.pop_front()
+    }
+}
+
+
+// resolves to Halted, Faulted, or WaitingInput if the input was closed while the machine wanted more
+pub struct RunFuture<'a, IN, OUT, OBS> {
+    computer: &'a mut IntcodeComputer<IN, OUT, OBS>,
+    // read but not yet accepted by the output
+    pending_output: Option<Word>,
+}
+
+impl<IN, OUT, OBS> Future for RunFuture<'_, IN, OUT, OBS>
+where IN: AsyncInput + Unpin, OUT: AsyncOutput + Unpin, OBS: Observer + Unpin {
+    type Output = ExecState;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<ExecState> {
+        let this = self.get_mut();
+        let computer = &mut *this.computer;
+        loop {
+            let inst = match computer.parse_next_instruction() {
+                Ok(inst) => inst,
+                Err(fault) => return Poll::Ready(ExecState::Faulted(fault)),
+            };
+            match inst.op {
+                Operation::Halt => {
+                    computer.halt(&inst);
+                    return Poll::Ready(ExecState::Halted);
+                },
+                Operation::Input => match computer.input_mut().poll_read(cx) {
+                    Poll::Ready(Some(v)) => computer.finish_input(&inst, v),
+                    Poll::Ready(None) => return Poll::Ready(ExecState::WaitingInput),
+                    Poll::Pending => return Poll::Pending,
+                },
+                Operation::Output => {
+                    let val = match this.pending_output.take() {
+                        Some(val) => val,
+                        None => computer.begin_output(&inst),
+                    };
+                    match computer.output_mut().poll_write(cx, val) {
+                        Poll::Ready(()) => computer.finish_output(&inst, val),
+                        Poll::Pending => {
+                            this.pending_output = Some(val);
+                            return Poll::Pending;
+                        },
+                    }
+                },
+                _ => computer.execute_one_instruction(inst),
+            }
+        }
+    }
+}
+
+impl<IN, OUT, OBS> IntcodeComputer<IN, OUT, OBS>
+where IN: AsyncInput + Unpin, OUT: AsyncOutput + Unpin, OBS: Observer + Unpin {
+
+    pub fn run_async(&mut self) -> RunFuture<'_, IN, OUT, OBS> {
+        RunFuture { computer: self, pending_output: None }
+    }
+}
+
+#[test]
+fn test_feedback_loop() {
+    use super::executor::LocalExecutor;
+
+    let prog: Vec<Word> = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5"
+        .split(',').map(|x| x.parse().unwrap()).collect();
+    let (senders, receivers): (Vec<_>, Vec<_>) = (0..5).map(|_| channel(1)).unzip();
+    for (sender, phase) in senders.iter().zip([9, 8, 7, 6, 5]) {
+        sender.push(phase);
+    }
+    senders[0].push(0);
+    let last = receivers[0].state.clone();
+
+    let mut senders: VecDeque<AsyncSender> = senders.into();
+    senders.rotate_left(1);
+    let mut computers: Vec<_> = receivers.into_iter().zip(senders)
+        .map(|(input, output)| IntcodeComputer::new(prog.clone(), input, output))
+        .collect();
+
+    let mut executor = LocalExecutor::new();
+    for computer in computers.iter_mut() {
+        executor.spawn(async move {
+            assert_eq!(computer.run_async().await, ExecState::Halted);
+        });
+    }
+    assert_eq!(executor.run(), 0);
+    assert_eq!(last.borrow().queue.back(), Some(&139629729));
+}
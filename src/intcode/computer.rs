@@ -1,19 +1,51 @@
 use super::inst::*;
 use super::io::*;
+use super::device::Device;
+use super::ext::{HostArgs, HostCall, MachineAccess};
+use super::isa::{Fault, IsaProfile};
+use super::observer::{NoopObserver, Observer};
+use super::smc::{CodeGuard, CodePolicy, CodeWrite};
 
 use std::collections::BTreeMap;
+use std::ops::Range;
 
-pub struct IntcodeComputer<IN, OUT> {
+const MAX_EXTENSION_ARITY: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExecState {
+    Running,
+    WaitingInput,
+    Halted,
+    // the pc stays at the faulting instruction
+    Faulted(Fault),
+}
+
+impl ExecState {
+    // halted or faulted, it will never run again
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ExecState::Halted | ExecState::Faulted(_))
+    }
+}
+
+pub struct IntcodeComputer<IN, OUT, OBS = NoopObserver> {
     mem: BTreeMap<usize, Word>,
     pc: usize,
     relative_base: usize,
 
     input: IN,
     output: OUT,
+
+    devices: Vec<(Range<usize>, Box<dyn Device + Send>)>,
+    extensions: BTreeMap<Word, Box<dyn HostCall + Send>>,
+    writes: Option<Vec<(usize, Word)>>,
+    profile: Option<IsaProfile>,
+    code_guard: Option<CodeGuard>,
+    frozen: BTreeMap<usize, Word>,
+
+    observer: OBS,
 }
 
-impl<IN, OUT> IntcodeComputer<IN, OUT>
-where IN: Input, OUT: Output {
+impl<IN, OUT> IntcodeComputer<IN, OUT> {
 
     pub fn new(initial_mem: Vec<Word>, input: IN, output: OUT) -> Self {
         let mem : BTreeMap<usize, Word> =
@@ -22,97 +54,395 @@ where IN: Input, OUT: Output {
             mem,
             pc: 0,
             relative_base: 0,
-            input, output
+            input, output,
+            devices: Vec::new(),
+            extensions: BTreeMap::new(),
+            writes: None,
+            profile: None,
+            code_guard: None,
+            frozen: BTreeMap::new(),
+            observer: NoopObserver,
         }
     }
+}
+
+impl<IN, OUT, OBS: Observer> IntcodeComputer<IN, OUT, OBS> {
+
+    pub fn with_observer<O: Observer>(self, observer: O) -> IntcodeComputer<IN, OUT, O> {
+        IntcodeComputer {
+            mem: self.mem,
+            pc: self.pc,
+            relative_base: self.relative_base,
+            input: self.input,
+            output: self.output,
+            devices: self.devices,
+            extensions: self.extensions,
+            writes: self.writes,
+            profile: self.profile,
+            code_guard: self.code_guard,
+            frozen: self.frozen,
+            observer,
+        }
+    }
+
+    pub fn observer_ref(&self) -> &OBS {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut OBS {
+        &mut self.observer
+    }
 
     pub fn input_ref(&self) -> &IN {
         &self.input
     }
 
     pub fn output_ref(&self) -> &OUT {
-        &&self.output
+        &self.output
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> usize {
+        self.relative_base
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub fn set_relative_base(&mut self, relative_base: usize) {
+        self.relative_base = relative_base;
+    }
+
+    pub fn input_mut(&mut self) -> &mut IN {
+        &mut self.input
+    }
+
+    pub fn output_mut(&mut self) -> &mut OUT {
+        &mut self.output
+    }
+
+    // map `range` to `device`: reads and writes of those addresses are handled by the device
+    // instead of memory. instructions are always fetched from memory
+    pub fn attach_device<D: Device + Send + 'static>(&mut self, range: Range<usize>, device: D) {
+        assert!(self.devices.iter().all(|(r, _)| r.end <= range.start || range.end <= r.start),
+                "device range {:?} overlaps another device", range);
+        self.devices.push((range, Box::new(device)));
+    }
+
+    // dispatch `opcode` to `handler`. built-in opcodes cannot be overridden, and
+    // registered opcodes run under any ISA profile. like the built-ins, an extension
+    // takes at most 3 operands, one per parameter mode digit
+    pub fn register_extension<H: HostCall + Send + 'static>(&mut self, opcode: Word, handler: H) {
+        assert!((0..100).contains(&opcode) && !Operation::is_builtin_opcode(opcode),
+                "cannot register opcode {}", opcode);
+        assert!(handler.arity() <= MAX_EXTENSION_ARITY, "opcode {} takes {} operands, at most {} are supported",
+                opcode, handler.arity(), MAX_EXTENSION_ARITY);
+        self.extensions.insert(opcode, Box::new(handler));
+    }
+
+    // fault on opcodes and parameter modes outside `profile`. without a profile, unknown
+    // opcodes halt the machine
+    pub fn set_profile(&mut self, profile: IsaProfile) {
+        self.profile = Some(profile);
+    }
+
+    // track the addresses executed as code and apply `policy` to writes into them
+    pub fn guard_code(&mut self, policy: CodePolicy) {
+        self.code_guard = Some(CodeGuard::new(policy));
+    }
+
+    pub fn code_guard(&self) -> Option<&CodeGuard> {
+        self.code_guard.as_ref()
+    }
+
+    // start recording every memory write, see `take_writes`
+    pub fn record_writes(&mut self) {
+        self.writes.get_or_insert_with(Vec::new);
+    }
+
+    pub fn take_writes(&mut self) -> Vec<(usize, Word)> {
+        self.writes.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn device_at(&mut self, addr: usize) -> Option<(usize, &mut Box<dyn Device + Send>)> {
+        self.devices.iter_mut()
+            .find(|(range, _)| range.contains(&addr))
+            .map(|(range, device)| (addr - range.start, device))
+    }
+
+    // read memory directly, bypassing devices
+    pub fn peek(&self, addr: usize) -> Word {
+        self.mem.get(&addr).copied().unwrap_or(0)
+    }
+
+    // write memory directly, bypassing devices, observers and frozen cells
+    pub fn poke(&mut self, addr: usize, val: Word) {
+        self.mem.insert(addr, val);
+    }
+
+    // every memory cell that has been initialized or written
+    pub fn cells(&self) -> impl Iterator<Item = (usize, Word)> + '_ {
+        self.mem.iter().map(|(addr, val)| (*addr, *val))
+    }
+
+    // set `addr` to `val` and ignore the program's writes to it until `unfreeze`
+    pub fn freeze(&mut self, addr: usize, val: Word) {
+        self.frozen.insert(addr, val);
+        self.poke(addr, val);
+    }
+
+    pub fn unfreeze(&mut self, addr: usize) {
+        self.frozen.remove(&addr);
+    }
+
+    pub fn read_mem(&mut self, addr: usize) -> Word {
+        let val = match self.device_at(addr) {
+            Some((offset, device)) => device.read(offset),
+            None => self.peek(addr),
+        };
+        self.observer.on_read(addr, val);
+        val
     }
 
-    fn read_param(&self, param: Parameter) -> Word {
+    pub fn write_mem(&mut self, addr: usize, val: Word) {
+        let val = self.frozen.get(&addr).copied().unwrap_or(val);
+        if let Some(writes) = self.writes.as_mut() {
+            writes.push((addr, val));
+        }
+        self.observer.on_write(addr, val);
+        if let Some(guard) = self.code_guard.as_mut().filter(|g| g.is_code(addr)) {
+            let old = self.mem.get(&addr).copied().unwrap_or(0);
+            let write = CodeWrite { pc: self.pc, addr, old, new: val };
+            if guard.policy == CodePolicy::Log {
+                self.observer.on_code_write(&write);
+            }
+            guard.record(write);
+        }
+        match self.device_at(addr) {
+            Some((offset, device)) => device.write(offset, val),
+            None => { self.mem.insert(addr, val); },
+        }
+    }
+
+    fn param_address(&self, param: Parameter) -> Option<usize> {
         match param {
-            Parameter::AbsPosition(i) => self.mem.get(&i).copied().unwrap_or(0),
-            Parameter::RelPosition(i) =>
-                self.mem.get(&((self.relative_base as i64 + i) as usize)).copied().unwrap_or(0),
-            Parameter::Immediate(v) => v,
+            Parameter::AbsPosition(i) => Some(i),
+            Parameter::RelPosition(i) => Some((self.relative_base as i64 + i) as usize),
+            Parameter::Immediate(_) => None,
+        }
+    }
+
+    fn read_param(&mut self, param: Parameter) -> Word {
+        match (param, self.param_address(param)) {
+            (_, Some(addr)) => self.read_mem(addr),
+            (Parameter::Immediate(v), None) => v,
+            _ => unreachable!(),
         }
     }
 
     fn write_param(&mut self, param: Parameter, val: Word) {
-        match param {
-            Parameter::Immediate(_) => panic!("Cannot set with immediate param"),
-            Parameter::AbsPosition(i) => { self.mem.insert(i, val); },
-            Parameter::RelPosition(i) => {
-                self.mem.insert((self.relative_base as i64 + i) as usize, val);
-            },
+        match self.param_address(param) {
+            Some(addr) => self.write_mem(addr, val),
+            None => panic!("Cannot set with immediate param"),
         }
     }
 
-    fn execute_one_instruction(&mut self, inst: Instruction) {
+    fn fetched(&mut self, inst: &Instruction) {
+        self.observer.on_fetch(self.pc, inst);
+        if let Some(guard) = self.code_guard.as_mut() {
+            guard.mark_executed(self.pc, inst.op.instruction_len());
+        }
+    }
+
+    // Input and Output are left to the caller, which owns the io protocol
+    pub(super) fn execute_one_instruction(&mut self, inst: Instruction) {
+        self.fetched(&inst);
         let mut new_pc: Option<usize> = None;
         match inst.op {
-            Operation::Add =>
-                self.write_param(inst.params[2], self.read_param(inst.params[0]) + self.read_param(inst.params[1])),
-            Operation::Multiply =>
-                self.write_param(inst.params[2], self.read_param(inst.params[0]) * self.read_param(inst.params[1])),
-            Operation::LessThan =>
-                self.write_param(inst.params[2], if self.read_param(inst.params[0]) < self.read_param(inst.params[1]) { 1 } else { 0 }),
-            Operation::Equals =>
-                self.write_param(inst.params[2], if self.read_param(inst.params[0]) == self.read_param(inst.params[1]) { 1 } else { 0 }),
-            Operation::Input => {
-                let v = self.input.read().unwrap();
-                self.write_param(inst.params[0], v);
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => {
+                let a = self.read_param(inst.params[0]);
+                let b = self.read_param(inst.params[1]);
+                let val = match inst.op {
+                    Operation::Add => a + b,
+                    Operation::Multiply => a * b,
+                    Operation::LessThan => (a < b) as Word,
+                    _ => (a == b) as Word,
+                };
+                self.write_param(inst.params[2], val);
             },
-            Operation::Output =>
-                self.output.write(self.read_param(inst.params[0])),
-            Operation::JumpIfTrue => {
-                if self.read_param(inst.params[0]) != 0 {
-                    new_pc = Some(self.read_param(inst.params[1]) as usize);
-                }
-            },
-            Operation::JumpIfFalse => {
-                if self.read_param(inst.params[0]) == 0 {
+            Operation::Input | Operation::Output => panic!("io is handled by the caller"),
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let cond = self.read_param(inst.params[0]) != 0;
+                if cond == (inst.op == Operation::JumpIfTrue) {
                     new_pc = Some(self.read_param(inst.params[1]) as usize);
                 }
             },
             Operation::AdjustRelativeBase => {
                 let delta = self.read_param(inst.params[0]);
+                let old = self.relative_base;
                 self.relative_base = (self.relative_base as i64 + delta) as usize;
+                self.observer.on_relative_base(old, self.relative_base);
+            },
+            Operation::Extension { opcode, .. } => {
+                let mut handler = self.extensions.remove(&opcode).unwrap();
+                handler.call(&mut HostArgs::new(&inst.params, self));
+                self.extensions.insert(opcode, handler);
             },
             Operation::Halt => panic!("should not arrive here"),
         }
         if let Some(new_pc) = new_pc {
+            self.observer.on_jump(self.pc, new_pc);
             self.pc = new_pc;
         } else {
             self.pc += inst.op.instruction_len()
         }
     }
 
-    fn parse_next_instruction(&self) -> Option<Instruction> {
-        let inst = self.read_param(Parameter::AbsPosition(self.pc));
-        let op = Operation::from(inst % 100);
+    pub(super) fn finish_input(&mut self, inst: &Instruction, val: Word) {
+        self.fetched(inst);
+        self.observer.on_input(val);
+        self.write_param(inst.params[0], val);
+        self.pc += inst.op.instruction_len();
+    }
+
+    // the caller must pass the result to `finish_output`, even if it has to wait
+    // before it can write it
+    pub(super) fn begin_output(&mut self, inst: &Instruction) -> Word {
+        self.fetched(inst);
+        self.read_param(inst.params[0])
+    }
+
+    pub(super) fn finish_output(&mut self, inst: &Instruction, val: Word) {
+        self.observer.on_output(val);
+        self.pc += inst.op.instruction_len();
+    }
+
+    pub(super) fn halt(&mut self, inst: &Instruction) {
+        self.fetched(inst);
+        self.observer.on_halt(self.pc);
+    }
+
+    pub(super) fn parse_next_instruction(&self) -> Result<Instruction, Fault> {
+        let inst = self.peek(self.pc);
+        let opcode = inst % 100;
+        // extensions come from the host, the profile restricts the built-in dialect
+        if !self.extensions.contains_key(&opcode) && self.profile.is_some_and(|p| !p.allows_opcode(opcode)) {
+            return Err(Fault::IllegalOpcode { pc: self.pc, opcode });
+        }
+        let op = match self.extensions.get(&opcode) {
+            Some(handler) => Operation::Extension { opcode, arity: handler.arity().min(MAX_EXTENSION_ARITY) },
+            None => Operation::from(opcode),
+        };
         let mut params = Vec::<Parameter>::new();
         for i in 0..(op.instruction_len()-1) {
-            let v = self.read_param(Parameter::AbsPosition(self.pc+i+1));
-            let mode = (inst / 100 / (10i64.pow(i as u32))) % 10;
-            params.push(Parameter::new(mode as i8, v));
+            let v = self.peek(self.pc+i+1);
+            let mode = ((inst / 100 / (10i64.pow(i as u32))) % 10) as i8;
+            if !Parameter::is_valid_mode(mode) || self.profile.is_some_and(|p| !p.allows_mode(mode)) {
+                return Err(Fault::IllegalMode { pc: self.pc, mode });
+            }
+            let addr = match mode {
+                1 => None,
+                2 => Some((self.relative_base as Word).saturating_add(v)),
+                _ => Some(v),
+            };
+            if let Some(addr) = addr.filter(|addr| *addr < 0) {
+                return Err(Fault::BadAddress { pc: self.pc, addr });
+            }
+            params.push(Parameter::new(mode, v));
         }
-        Some(Instruction { op, params })
+        let inst = Instruction { op, params };
+        self.check_code_write(&inst)?;
+        Ok(inst)
     }
 
-    pub fn run_until_finish(&mut self) {
-        while let Some(inst) = self.parse_next_instruction() {
-            // dbg!(&inst);
-            if inst.op == Operation::Halt {
-                break;
-            } else {
+    // trapped writes fault before the instruction runs. writes by extensions are only recorded
+    fn check_code_write(&self, inst: &Instruction) -> Result<(), Fault> {
+        let Some(guard) = self.code_guard.as_ref().filter(|g| g.policy == CodePolicy::Trap) else { return Ok(()) };
+        let target = match inst.op {
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => inst.params[2],
+            Operation::Input => inst.params[0],
+            _ => return Ok(()),
+        };
+        // the instruction counts as executed code before its own write, as it does for `fetched`
+        let current = self.pc..self.pc + inst.op.instruction_len();
+        match self.param_address(target) {
+            Some(addr) if guard.is_code(addr) || current.contains(&addr) => Err(Fault::CodeWrite { pc: self.pc, addr }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<IN, OUT, OBS> IntcodeComputer<IN, OUT, OBS>
+where IN: Input, OUT: Output, OBS: Observer {
+
+    // execute one instruction. when the input is empty, the pc stays at the input
+    // instruction so that it can be retried after more input is provided
+    pub fn step(&mut self) -> ExecState {
+        let inst = match self.parse_next_instruction() {
+            Ok(inst) => inst,
+            Err(fault) => return ExecState::Faulted(fault),
+        };
+        match inst.op {
+            Operation::Halt => {
+                self.halt(&inst);
+                ExecState::Halted
+            },
+            Operation::Input => match self.input.read() {
+                Some(v) => {
+                    self.finish_input(&inst, v);
+                    ExecState::Running
+                },
+                None => ExecState::WaitingInput,
+            },
+            Operation::Output => {
+                let val = self.begin_output(&inst);
+                self.output.write(val);
+                self.finish_output(&inst, val);
+                ExecState::Running
+            },
+            _ => {
                 self.execute_one_instruction(inst);
+                ExecState::Running
+            },
+        }
+    }
+
+    pub fn run_until_blocked(&mut self) -> ExecState {
+        loop {
+            match self.step() {
+                ExecState::Running => continue,
+                state => return state,
             }
         }
     }
+
+    pub fn run_until_finish(&mut self) {
+        match self.run_until_blocked() {
+            ExecState::WaitingInput => panic!("input exhausted"),
+            ExecState::Faulted(fault) => panic!("{}", fault),
+            _ => (),
+        }
+    }
+}
+
+impl<IN, OUT, OBS: Observer> MachineAccess for IntcodeComputer<IN, OUT, OBS> {
+    fn read_param(&mut self, param: Parameter) -> Word {
+        IntcodeComputer::read_param(self, param)
+    }
+
+    fn write_param(&mut self, param: Parameter, val: Word) {
+        IntcodeComputer::write_param(self, param, val)
+    }
+
+    fn read_mem(&mut self, addr: usize) -> Word {
+        IntcodeComputer::read_mem(self, addr)
+    }
+
+    fn write_mem(&mut self, addr: usize, val: Word) {
+        IntcodeComputer::write_mem(self, addr, val)
+    }
 }
@@ -0,0 +1,696 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::inst::{Cond, Instruction, Operation, Parameter, Word};
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    Const(Word),
+    Var(String),
+    Not(Box<Expr>),
+    Bin(&'static str, Box<Expr>, Box<Expr>),
+}
+
+fn precedence(op: &str) -> u8 {
+    match op {
+        "*" => 3,
+        "+" | "-" => 2,
+        _ => 1,
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", v),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Not(e) if matches!(**e, Expr::Bin(..)) => write!(f, "!({})", e),
+            Expr::Not(e) => write!(f, "!{}", e),
+            Expr::Bin(op, a, b) => {
+                // operators are left associative
+                match &**a {
+                    Expr::Bin(inner, ..) if precedence(inner) < precedence(op) => write!(f, "({})", a)?,
+                    _ => write!(f, "{}", a)?,
+                }
+                write!(f, " {} ", op)?;
+                match &**b {
+                    Expr::Bin(inner, ..) if precedence(inner) <= precedence(op) => write!(f, "({})", b),
+                    _ => write!(f, "{}", b),
+                }
+            },
+        }
+    }
+}
+
+// drops identities and turns `x + -1` into `x - 1`. constants are not folded, since
+// the programs compute with immediates on purpose
+fn bin(op: &'static str, a: Expr, b: Expr) -> Expr {
+    match (op, &a, &b) {
+        ("+", Expr::Const(0), _) | ("*", Expr::Const(1), _) => b,
+        ("+", _, Expr::Const(0)) | ("*", _, Expr::Const(1)) => a,
+        ("+", _, Expr::Const(v)) if *v < 0 && *v != Word::MIN => Expr::Bin("-", Box::new(a), Box::new(Expr::Const(-v))),
+        ("+", Expr::Const(v), _) if *v < 0 && *v != Word::MIN => Expr::Bin("-", Box::new(b), Box::new(Expr::Const(-v))),
+        _ => Expr::Bin(op, Box::new(a), Box::new(b)),
+    }
+}
+
+fn not(e: Expr) -> Expr {
+    let negated = |op| match op {
+        "==" => Some("!="),
+        "!=" => Some("=="),
+        "<" => Some(">="),
+        ">=" => Some("<"),
+        _ => None,
+    };
+    match e {
+        Expr::Bin(op, a, b) if negated(op).is_some() => Expr::Bin(negated(op).unwrap(), a, b),
+        Expr::Not(e) => *e,
+        e => Expr::Not(Box::new(e)),
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Stmt {
+    // the offset is set for writes relative to the relative base, which may be call arguments
+    Assign(usize, Expr, Expr, Option<Word>),
+    Input(usize, Expr),
+    Output(usize, Expr),
+    Rb(usize, Expr),
+    // no arguments when they could not be recovered
+    Call(usize, usize, Option<Vec<Expr>>),
+    Return(usize),
+    Halt(usize),
+    IndirectJump(usize, Option<Expr>, Expr),
+    Goto(usize, Option<Expr>, usize),
+    Break(usize, Option<Expr>),
+    Continue(usize, Option<Expr>),
+    If(usize, Expr, Vec<Stmt>, Vec<Stmt>),
+    While(usize, Expr, Vec<Stmt>),
+    DoWhile(usize, Vec<Stmt>, Expr),
+}
+
+impl Stmt {
+    fn addr(&self) -> usize {
+        match self {
+            Stmt::Assign(a, ..) | Stmt::Input(a, _) | Stmt::Output(a, _) | Stmt::Rb(a, _) | Stmt::Call(a, ..)
+                | Stmt::Return(a) | Stmt::Halt(a) | Stmt::IndirectJump(a, ..) | Stmt::Goto(a, ..)
+                | Stmt::Break(a, _) | Stmt::Continue(a, _) | Stmt::If(a, ..) | Stmt::While(a, ..)
+                | Stmt::DoWhile(a, ..) => *a,
+        }
+    }
+}
+
+// the value written by `out0 = constant`, the return address of a call
+fn return_address(inst: &Instruction) -> Option<Word> {
+    match (inst.op, &inst.params[..]) {
+        (Operation::Add, [Parameter::Immediate(a), Parameter::Immediate(b), Parameter::RelPosition(0)]) => a.checked_add(*b),
+        (Operation::Multiply, [Parameter::Immediate(a), Parameter::Immediate(b), Parameter::RelPosition(0)]) => a.checked_mul(*b),
+        _ => None,
+    }
+}
+
+struct Function {
+    entry: usize,
+    insts: BTreeMap<usize, Instruction>,
+    // call sites, by the address of the jump, and the instructions storing their return addresses
+    calls: BTreeMap<usize, usize>,
+    setups: BTreeSet<usize>,
+    // words reserved by the prologue. None for the entry point, whose relative base is absolute
+    frame: Option<Word>,
+    arity: Word,
+    // the relative base before each instruction, relative to the entry
+    deltas: BTreeMap<usize, Option<Word>>,
+}
+
+impl Function {
+    // a call writes its return address to [rb+0], arguments to [rb+1].. and jumps
+    fn discover(mem: &[Word], entry: usize, is_main: bool) -> Function {
+        let mut insts = BTreeMap::new();
+        let mut calls = BTreeMap::new();
+        let mut setups = BTreeSet::new();
+        let mut pending = vec![entry];
+        while let Some(start) = pending.pop() {
+            let mut pc = start;
+            let mut recent: Vec<(usize, Option<Word>)> = Vec::new();
+            while !insts.contains_key(&pc) {
+                let Some(inst) = Instruction::decode(mem, pc) else { break };
+                let next = pc + inst.op.instruction_len();
+                let cond = inst.jump_cond();
+                let target = match inst.params.get(1) {
+                    Some(Parameter::Immediate(t)) if cond.is_some() && *t >= 0 => Some(*t as usize),
+                    _ => None,
+                };
+                recent.push((pc, return_address(&inst)));
+                insts.insert(pc, inst.clone());
+                if inst.op == Operation::Halt {
+                    break;
+                }
+                match (cond, target) {
+                    (Some(Cond::Always), Some(t)) => {
+                        let setup = recent.iter().rev().take(5).find(|(_, ret)| *ret == Some(next as Word));
+                        match setup {
+                            Some((setup, _)) => {
+                                calls.insert(pc, t);
+                                setups.insert(*setup);
+                            },
+                            None => {
+                                pending.push(t);
+                                break;
+                            },
+                        }
+                    },
+                    (Some(Cond::Always), None) => break,
+                    (Some(Cond::Test(..)), Some(t)) => pending.push(t),
+                    _ => (),
+                }
+                pc = next;
+            }
+        }
+
+        let frame = match insts.get(&entry) {
+            _ if is_main => None,
+            Some(Instruction { op: Operation::AdjustRelativeBase, params }) => match params[0] {
+                Parameter::Immediate(n) if n > 0 => Some(n),
+                _ => Some(0),
+            },
+            _ => Some(0),
+        };
+        let mut deltas = BTreeMap::new();
+        let mut delta: Option<Word> = Some(0);
+        for (addr, inst) in insts.iter() {
+            deltas.insert(*addr, delta);
+            if inst.op == Operation::AdjustRelativeBase {
+                delta = match inst.params[0] {
+                    Parameter::Immediate(v) => delta.and_then(|d| d.checked_add(v)),
+                    _ => None,
+                };
+            }
+        }
+        let mut function = Function { entry, insts, calls, setups, frame, arity: 0, deltas };
+        function.arity = function.count_arguments();
+        function
+    }
+
+    // frame slots that are read before they are written
+    fn count_arguments(&self) -> Word {
+        let Some(frame) = self.frame else { return 0 };
+        let mut first_access: BTreeMap<Word, bool> = BTreeMap::new();
+        for (addr, inst) in self.insts.iter() {
+            let Some(delta) = self.deltas[addr] else { continue };
+            let (reads, write) = inst.op.roles();
+            for (i, is_read) in reads.iter().map(|i| (*i, true)).chain(write.map(|i| (i, false))) {
+                let Parameter::RelPosition(o) = inst.params[i] else { continue };
+                // a slot that overflows is far outside the frame
+                if let Some(slot) = delta.checked_add(o).filter(|slot| (1..frame).contains(slot)) {
+                    first_access.entry(slot).or_insert(is_read);
+                }
+            }
+        }
+        first_access.into_iter().filter(|(_, is_read)| *is_read).map(|(e, _)| e).max().unwrap_or(0)
+    }
+
+    fn name(&self) -> String {
+        match self.frame {
+            None => "main".to_string(),
+            Some(_) => format!("fn_{}", self.entry),
+        }
+    }
+
+    // relative operands whose slot is unknown or overflows keep their raw `rb[o]` form
+    fn var(&self, addr: usize, param: Parameter) -> Expr {
+        let slot = |o: Word| self.deltas[&addr].and_then(|d| d.checked_add(o));
+        Expr::Var(match (param, self.frame) {
+            (Parameter::Immediate(v), _) => return Expr::Const(v),
+            (Parameter::AbsPosition(a), _) => format!("m[{}]", a),
+            (Parameter::RelPosition(o), frame) => match (frame, slot(o)) {
+                (_, None) => format!("rb[{}]", o),
+                (None, Some(e)) => format!("m[{}]", e),
+                (Some(_), Some(0)) => "ret".to_string(),
+                (Some(_), Some(e)) if e > 0 && e <= self.arity => format!("arg{}", e),
+                (Some(frame), Some(e)) if e > 0 && e < frame => format!("local{}", e),
+                (Some(frame), Some(e)) if e >= frame => format!("out{}", e - frame),
+                _ => format!("rb[{}]", o),
+            },
+        })
+    }
+}
+
+// a scratch write waiting to be folded into the next instruction: cell, value, address
+type Pending = Option<(usize, Expr, usize)>;
+
+fn flush(pending: &mut Pending, out: &mut Vec<Stmt>) {
+    if let Some((x, expr, at)) = pending.take() {
+        out.push(Stmt::Assign(at, Expr::Var(format!("m[{}]", x)), expr, None));
+    }
+}
+
+struct Decompiler {
+    functions: Vec<Function>,
+    // written cells whose every read directly follows the write, so that they can be folded
+    scratch: BTreeSet<usize>,
+}
+
+impl Decompiler {
+    fn new(mem: &[Word]) -> Self {
+        let mut functions: Vec<Function> = Vec::new();
+        let mut entries = vec![0];
+        while let Some(entry) = entries.pop() {
+            if functions.iter().any(|f| f.entry == entry) {
+                continue;
+            }
+            let function = Function::discover(mem, entry, functions.is_empty());
+            entries.extend(function.calls.values());
+            functions.push(function);
+        }
+        functions[1..].sort_by_key(|f| f.entry);
+
+        let all: BTreeMap<usize, &Instruction> = functions.iter()
+            .flat_map(|f| f.insts.iter())
+            .collect::<BTreeMap<_, _>>()
+            .into_iter().map(|(a, i)| (*a, i)).collect();
+        let by_end: BTreeMap<usize, usize> = all.iter().map(|(a, i)| (a + i.op.instruction_len(), *a)).collect();
+        let targets: BTreeSet<usize> = all.values()
+            .filter(|i| i.jump_cond().is_some())
+            .filter_map(|i| match i.params[1] {
+                Parameter::Immediate(t) if t >= 0 => Some(t as usize),
+                _ => None,
+            })
+            .collect();
+        let writes = |inst: &Instruction, x: usize| inst.op.roles().1.map(|i| inst.params[i]) == Some(Parameter::AbsPosition(x));
+        let mut scratch = BTreeSet::new();
+        let mut unfoldable = BTreeSet::new();
+        for (addr, inst) in all.iter() {
+            for i in inst.op.roles().0 {
+                let Parameter::AbsPosition(x) = inst.params[*i] else { continue };
+                let prev = by_end.get(addr).map(|p| all[p]);
+                if !targets.contains(addr) && prev.is_some_and(|p| writes(p, x)) {
+                    scratch.insert(x);
+                } else {
+                    unfoldable.insert(x);
+                }
+            }
+        }
+        // code is never scratch
+        scratch.retain(|x| !unfoldable.contains(x)
+                       && all.range(..=*x).next_back().is_none_or(|(a, i)| a + i.op.instruction_len() <= *x));
+        Decompiler { functions, scratch }
+    }
+
+    fn read(f: &Function, addr: usize, param: Parameter, pending: &mut Pending, start: &mut usize) -> Expr {
+        match pending.take() {
+            Some((x, expr, at)) if param == Parameter::AbsPosition(x) => {
+                *start = at;
+                expr
+            },
+            other => {
+                *pending = other;
+                f.var(addr, param)
+            },
+        }
+    }
+
+    // one statement per instruction, with scratch writes folded and jumps as gotos
+    fn flatten(&self, f: &Function) -> Vec<Stmt> {
+        let mut out = Vec::new();
+        let mut pending: Pending = None;
+        for (addr, inst) in f.insts.iter() {
+            let addr = *addr;
+            if f.setups.contains(&addr) {
+                continue;
+            }
+            let mut start = addr;
+            let p = &inst.params;
+            let stmt = match inst.op {
+                Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => {
+                    let op = match inst.op {
+                        Operation::Add => "+",
+                        Operation::Multiply => "*",
+                        Operation::LessThan => "<",
+                        _ => "==",
+                    };
+                    let a = Self::read(f, addr, p[0], &mut pending, &mut start);
+                    let b = Self::read(f, addr, p[1], &mut pending, &mut start);
+                    let value = bin(op, a, b);
+                    match p[2] {
+                        Parameter::AbsPosition(x) if self.scratch.contains(&x) => {
+                            flush(&mut pending, &mut out);
+                            pending = Some((x, value, start));
+                            continue;
+                        },
+                        Parameter::RelPosition(o) => Some(Stmt::Assign(start, f.var(addr, p[2]), value, Some(o))),
+                        _ => Some(Stmt::Assign(start, f.var(addr, p[2]), value, None)),
+                    }
+                },
+                Operation::Input => Some(Stmt::Input(start, f.var(addr, p[0]))),
+                Operation::Output => {
+                    let value = Self::read(f, addr, p[0], &mut pending, &mut start);
+                    Some(Stmt::Output(start, value))
+                },
+                Operation::AdjustRelativeBase => match (p[0], f.deltas[&addr]) {
+                    // already accounted for in the names of frame slots
+                    (Parameter::Immediate(_), Some(_)) => None,
+                    (param, _) => {
+                        let delta = Self::read(f, addr, param, &mut pending, &mut start);
+                        Some(Stmt::Rb(start, delta))
+                    },
+                },
+                Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                    let cond = match inst.jump_cond().unwrap() {
+                        Cond::Never => None,
+                        Cond::Always => Some(None),
+                        Cond::Test(param, if_true) => {
+                            let value = Self::read(f, addr, param, &mut pending, &mut start);
+                            Some(Some(if if_true { value } else { not(value) }))
+                        },
+                    };
+                    match (cond, p[1]) {
+                        (None, _) => None,
+                        (Some(None), _) if f.calls.contains_key(&addr) => {
+                            let callee = f.calls[&addr];
+                            let arity = self.functions.iter().find(|f| f.entry == callee).map_or(0, |f| f.arity);
+                            flush(&mut pending, &mut out);
+                            let (args, first) = match take_arguments(&mut out, arity) {
+                                Some((args, first)) => (Some(args), first),
+                                None => (None, None),
+                            };
+                            Some(Stmt::Call(first.unwrap_or(start), callee, args))
+                        },
+                        (Some(cond), Parameter::Immediate(t)) if t >= 0 => Some(Stmt::Goto(start, cond, t as usize)),
+                        (Some(None), Parameter::RelPosition(o))
+                            if f.frame.is_some() && o.checked_neg().is_some_and(|o| f.deltas[&addr] == Some(o)) =>
+                            Some(Stmt::Return(start)),
+                        (Some(cond), target) => {
+                            let target = Self::read(f, addr, target, &mut pending, &mut start);
+                            Some(Stmt::IndirectJump(start, cond, target))
+                        },
+                    }
+                },
+                _ => Some(Stmt::Halt(start)),
+            };
+            flush(&mut pending, &mut out);
+            out.extend(stmt);
+        }
+        flush(&mut pending, &mut out);
+        out
+    }
+}
+
+// the assignments to out1..=outN directly before a call become its arguments.
+// also returns the address of the first of them. None if they are not all there
+fn take_arguments(out: &mut Vec<Stmt>, arity: Word) -> Option<(Vec<Expr>, Option<usize>)> {
+    if arity == 0 {
+        return Some((Vec::new(), None));
+    }
+    let mut args: BTreeMap<Word, Expr> = BTreeMap::new();
+    for stmt in out.iter().rev().take(arity as usize) {
+        match stmt {
+            Stmt::Assign(_, _, value, Some(o)) if (1..=arity).contains(o) && !args.contains_key(o) => {
+                args.insert(*o, value.clone());
+            },
+            _ => return None,
+        }
+    }
+    if args.len() as Word != arity {
+        return None;
+    }
+    let first = out.len() - args.len();
+    let addr = out[first].addr();
+    out.truncate(first);
+    Some((args.into_values().collect(), Some(addr)))
+}
+
+// recovers if, while and do-while from the gotos of a flattened function
+struct Structurer {
+    stmts: Vec<Stmt>,
+    index: BTreeMap<usize, usize>,
+    // header and exit address of the enclosing loops
+    loops: Vec<(usize, Option<usize>)>,
+}
+
+impl Structurer {
+    fn new(mut stmts: Vec<Stmt>) -> Self {
+        // instructions without a statement, like frame adjustments, fall through to the next one
+        let addrs: Vec<usize> = stmts.iter().map(Stmt::addr).collect();
+        for stmt in stmts.iter_mut() {
+            if let Stmt::Goto(_, _, t) = stmt {
+                if let Some(next) = addrs.get(addrs.partition_point(|a| a < t)) {
+                    *t = *next;
+                }
+            }
+        }
+        let mut index = BTreeMap::new();
+        for (i, stmt) in stmts.iter().enumerate() {
+            index.entry(stmt.addr()).or_insert(i);
+        }
+        Structurer { stmts, index, loops: Vec::new() }
+    }
+
+    fn target(&self, i: usize) -> Option<usize> {
+        match self.stmts[i] {
+            Stmt::Goto(_, _, t) => Some(t),
+            _ => None,
+        }
+    }
+
+    // the index of `addr`, if it lies within lo..=hi
+    fn index_within(&self, addr: usize, lo: usize, hi: usize) -> Option<usize> {
+        self.index.get(&addr).copied().filter(|i| (lo..=hi).contains(i))
+    }
+
+    fn is_loop_edge(&self, addr: usize) -> bool {
+        self.loops.last().is_some_and(|(header, exit)| *header == addr || *exit == Some(addr))
+    }
+
+    fn structure(&mut self, lo: usize, hi: usize, skip: Option<usize>) -> Vec<Stmt> {
+        let mut out = Vec::new();
+        let mut i = lo;
+        while i < hi {
+            let addr = self.stmts[i].addr();
+            let back_edge = (i..hi).rev().find(|j| self.target(*j) == Some(addr));
+            if let Some(j) = back_edge.filter(|_| skip != Some(i)) {
+                let exit = self.stmts.get(j + 1).map(Stmt::addr);
+                self.loops.push((addr, exit));
+                let stmt = match (self.stmts[i].clone(), self.stmts[j].clone()) {
+                    (Stmt::Goto(_, Some(cond), t), Stmt::Goto(_, None, _)) if i < j && Some(t) == exit =>
+                        Stmt::While(addr, not(cond), self.structure(i + 1, j, None)),
+                    (_, Stmt::Goto(_, cond, _)) =>
+                        Stmt::DoWhile(addr, self.structure(i, j, Some(i)), cond.unwrap_or(Expr::Const(1))),
+                    _ => unreachable!(),
+                };
+                self.loops.pop();
+                out.push(stmt);
+                i = j + 1;
+                continue;
+            }
+
+            let stmt = self.stmts[i].clone();
+            i += 1;
+            let Stmt::Goto(at, cond, t) = stmt else {
+                out.push(stmt);
+                continue;
+            };
+            match self.loops.last().copied() {
+                Some((_, exit)) if exit == Some(t) => {
+                    out.push(Stmt::Break(at, cond));
+                    continue;
+                },
+                Some((header, _)) if header == t => {
+                    out.push(Stmt::Continue(at, cond));
+                    continue;
+                },
+                _ => (),
+            }
+            let (Some(cond), Some(then_end)) = (cond.clone(), self.index_within(t, i, hi)) else {
+                out.push(Stmt::Goto(at, cond, t));
+                continue;
+            };
+            // a then branch ending with a forward jump over an else branch, which may be empty
+            let else_end = match self.stmts.get(then_end.wrapping_sub(1)) {
+                Some(Stmt::Goto(_, None, u)) if then_end > i && !self.is_loop_edge(*u) =>
+                    self.index_within(*u, then_end, hi),
+                _ => None,
+            };
+            let stmt = match else_end {
+                Some(else_end) => {
+                    let then = self.structure(i, then_end - 1, None);
+                    let otherwise = self.structure(then_end, else_end, None);
+                    i = else_end;
+                    match then.is_empty() {
+                        true => Stmt::If(at, cond, otherwise, Vec::new()),
+                        false => Stmt::If(at, not(cond), then, otherwise),
+                    }
+                },
+                None => {
+                    let then = self.structure(i, then_end, None);
+                    i = then_end;
+                    Stmt::If(at, not(cond), then, Vec::new())
+                },
+            };
+            out.push(stmt);
+        }
+        out
+    }
+}
+
+fn collect_labels(stmts: &[Stmt], labels: &mut BTreeSet<usize>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Goto(_, _, t) => { labels.insert(*t); },
+            Stmt::If(_, _, a, b) => {
+                collect_labels(a, labels);
+                collect_labels(b, labels);
+            },
+            Stmt::While(_, _, body) | Stmt::DoWhile(_, body, _) => collect_labels(body, labels),
+            _ => (),
+        }
+    }
+}
+
+fn print_block(stmts: &[Stmt], depth: usize, labels: &BTreeSet<usize>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let indent = "    ".repeat(depth);
+    let guarded = |cond: &Option<Expr>, what: String| match cond {
+        Some(cond) => format!("if ({}) {};", cond, what),
+        None => format!("{};", what),
+    };
+    for stmt in stmts {
+        if labels.contains(&stmt.addr()) {
+            writeln!(f, "{}L{}:", "    ".repeat(depth - 1), stmt.addr())?;
+        }
+        let line = match stmt {
+            Stmt::Assign(_, lhs, value, _) => format!("{} = {};", lhs, value),
+            Stmt::Input(_, lhs) => format!("{} = input();", lhs),
+            Stmt::Output(_, value) => format!("output({});", value),
+            Stmt::Rb(_, delta) => format!("rb += {};", delta),
+            Stmt::Call(_, target, Some(args)) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                format!("fn_{}({});", target, args.join(", "))
+            },
+            Stmt::Call(_, target, None) => format!("fn_{}(...);", target),
+            Stmt::Return(_) => "return;".to_string(),
+            Stmt::Halt(_) => "halt();".to_string(),
+            Stmt::IndirectJump(_, cond, target) => guarded(cond, format!("goto *{}", target)),
+            Stmt::Goto(_, cond, target) => guarded(cond, format!("goto L{}", target)),
+            Stmt::Break(_, cond) => guarded(cond, "break".to_string()),
+            Stmt::Continue(_, cond) => guarded(cond, "continue".to_string()),
+            Stmt::If(_, cond, then, otherwise) => {
+                writeln!(f, "{}if ({}) {{", indent, cond)?;
+                print_block(then, depth + 1, labels, f)?;
+                if !otherwise.is_empty() {
+                    writeln!(f, "{}}} else {{", indent)?;
+                    print_block(otherwise, depth + 1, labels, f)?;
+                }
+                "}".to_string()
+            },
+            Stmt::While(_, cond, body) => {
+                writeln!(f, "{}while ({}) {{", indent, cond)?;
+                print_block(body, depth + 1, labels, f)?;
+                "}".to_string()
+            },
+            Stmt::DoWhile(_, body, Expr::Const(1)) => {
+                writeln!(f, "{}while (1) {{", indent)?;
+                print_block(body, depth + 1, labels, f)?;
+                "}".to_string()
+            },
+            Stmt::DoWhile(_, body, cond) => {
+                writeln!(f, "{}do {{", indent)?;
+                print_block(body, depth + 1, labels, f)?;
+                format!("}} while ({});", cond)
+            },
+        };
+        writeln!(f, "{}{}", indent, line)?;
+    }
+    Ok(())
+}
+
+// the reachable code of a program as C-like pseudo-code, see `decompile`
+pub struct Listing {
+    functions: Vec<DecompiledFunction>,
+}
+
+struct DecompiledFunction {
+    name: String,
+    arity: Word,
+    frame: Option<Word>,
+    body: Vec<Stmt>,
+}
+
+// recover functions, structured control flow and expressions from a program.
+// a function is the target of a call, which writes its return address to [rb+0] and
+// arguments to [rb+1].., and may reserve a frame with its first instruction.
+// inside a function, frame slots are named ret, argN, localN, and outN for the callee frame
+pub fn decompile(mem: &[Word]) -> Listing {
+    let decompiler = Decompiler::new(mem);
+    let functions = decompiler.functions.iter().map(|f| {
+        let mut structurer = Structurer::new(decompiler.flatten(f));
+        let len = structurer.stmts.len();
+        let body = structurer.structure(0, len, None);
+        DecompiledFunction { name: f.name(), arity: f.arity, frame: f.frame, body }
+    }).collect();
+    Listing { functions }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            if let Some(frame) = function.frame.filter(|n| *n > 0) {
+                writeln!(f, "// frame of {} words", frame)?;
+            }
+            let args: Vec<String> = (1..=function.arity).map(|e| format!("arg{}", e)).collect();
+            writeln!(f, "fn {}({}) {{", function.name, args.join(", "))?;
+            let mut labels = BTreeSet::new();
+            collect_labels(&function.body, &mut labels);
+            print_block(&function.body, 1, &labels, f)?;
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_decompile() {
+    // counts the input down to zero, printing each value
+    let prog = vec![3, 20, 1008, 20, 0, 21, 1005, 21, 19, 4, 20, 101, -1, 20, 20, 1105, 1, 2, 0, 99];
+    let listing = decompile(&prog).to_string();
+    assert_eq!(listing, "fn main() {
+    m[20] = input();
+    while (m[20] != 0) {
+        output(m[20]);
+        m[20] = m[20] - 1;
+    }
+    halt();
+}
+");
+
+    let mem = super::program::Program::parse(include_str!("../../input/9")).unwrap().words;
+    let listing = decompile(&mem).to_string();
+    let recursion = listing.split("fn fn_922(arg1) {\n").nth(1).unwrap();
+    assert_eq!(recursion, "    if (arg1 >= 3) {
+        fn_922(arg1 - 1);
+        local2 = out1;
+        fn_922(arg1 - 3);
+        arg1 = out1 + local2;
+    } else {
+        arg1 = arg1;
+    }
+    return;
+}
+");
+
+    // a callee reading arg1 that the caller never stored, so the call has unknown arguments
+    let prog = vec![109, 10, 21101, 9, 0, 0, 1105, 1, 20, 99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    109, 3, 204, -2, 109, -3, 2105, 1, 0];
+    let listing = decompile(&prog).to_string();
+    assert!(listing.starts_with("fn main() {\n    fn_20(...);\n    halt();\n}\n"), "{}", listing);
+    assert!(listing.contains("fn fn_20(arg1) {\n    output(arg1);\n"));
+
+    // a relative base and a return address that overflow keep the raw instructions
+    let prog = vec![109, Word::MAX, 109, 1, 21101, Word::MAX, 1, 0, 99];
+    assert_eq!(decompile(&prog).to_string(), format!("fn main() {{
+    rb[0] = {} + 1;
+    halt();
+}}
+", Word::MAX));
+}
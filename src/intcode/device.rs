@@ -0,0 +1,128 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use super::inst::Word;
+
+// a host device mapped into the address space, see `IntcodeComputer::attach_device`.
+// `offset` is relative to the start of the mapped range
+pub trait Device {
+    fn read(&mut self, offset: usize) -> Word;
+    fn write(&mut self, offset: usize, val: Word);
+}
+
+// keep a handle to a device after attaching it, e.g. to inspect a framebuffer
+impl<D: Device> Device for Arc<Mutex<D>> {
+    fn read(&mut self, offset: usize) -> Word {
+        self.lock().unwrap().read(offset)
+    }
+
+    fn write(&mut self, offset: usize, val: Word) {
+        self.lock().unwrap().write(offset, val)
+    }
+}
+
+
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<Word>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer { width, height, pixels: vec![0; width * height] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pixels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Word {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Word]> {
+        self.pixels.chunks(self.width).take(self.height)
+    }
+}
+
+impl Device for Framebuffer {
+    fn read(&mut self, offset: usize) -> Word {
+        self.pixels.get(offset).copied().unwrap_or(0)
+    }
+
+    fn write(&mut self, offset: usize, val: Word) {
+        if let Some(pixel) = self.pixels.get_mut(offset) {
+            *pixel = val;
+        }
+    }
+}
+
+
+// reads the milliseconds elapsed since the clock was created; writes are ignored
+pub struct Clock {
+    start: Instant,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock { start: Instant::now() }
+    }
+}
+
+impl Device for Clock {
+    fn read(&mut self, _offset: usize) -> Word {
+        self.start.elapsed().as_millis() as Word
+    }
+
+    fn write(&mut self, _offset: usize, _val: Word) {}
+}
+
+
+// xorshift64* generator: every read returns the next non-negative number, a write reseeds it
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random { state: seed.max(1) }
+    }
+
+    // xorshift64*, non-negative
+    pub fn next_word(&mut self) -> Word {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545f4914f6cdd1d) >> 1) as Word
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, _offset: usize) -> Word {
+        self.next_word()
+    }
+
+    fn write(&mut self, _offset: usize, val: Word) {
+        self.state = (val as u64).max(1);
+    }
+}
+
+#[test]
+fn test_framebuffer() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+
+    // copy 3 inputs into the framebuffer at 1000, then read back the middle pixel
+    let prog = vec![3, 1000, 3, 1001, 3, 1002, 4, 1001, 99];
+    let framebuffer = Arc::new(Mutex::new(Framebuffer::new(3, 1)));
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[7, 8, 9]), BufferOutput::default());
+    computer.attach_device(1000..1003, framebuffer.clone());
+    computer.run_until_finish();
+    assert_eq!(&**computer.output_ref(), &[8]);
+    assert_eq!(framebuffer.lock().unwrap().rows().next().unwrap(), &[7, 8, 9]);
+}
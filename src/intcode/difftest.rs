@@ -0,0 +1,187 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::computer::ExecState;
+use super::engine::{self, Engine, SimpleMachine, StepEvent};
+use super::inst::Word;
+use super::isa::IsaProfile;
+use super::program::{LoadError, Program};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    // number of instructions both engines executed before this one
+    pub step: usize,
+    pub left: StepEvent,
+    pub right: StepEvent,
+}
+
+impl Divergence {
+    // which part of the step differs first: pc, memory write, output or state
+    pub fn kind(&self) -> &'static str {
+        if self.left.pc != self.right.pc {
+            "pc"
+        } else if self.left.writes != self.right.writes {
+            "memory write"
+        } else if self.left.output != self.right.output {
+            "output"
+        } else {
+            "state"
+        }
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} diverged at step {} (pc {}): {:?} vs {:?}",
+               self.kind(), self.step, self.left.pc, self.left, self.right)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Agreement {
+    // both engines stopped in the same state after this many steps
+    Stopped(usize),
+    // both engines were still running after the step limit
+    StepLimit,
+}
+
+// run two engines one instruction at a time until they stop or disagree
+pub fn lockstep(left: &mut dyn Engine, right: &mut dyn Engine, max_steps: usize) -> Result<Agreement, Box<Divergence>> {
+    for step in 0..max_steps {
+        let (l, r) = (left.step(), right.step());
+        if l != r {
+            return Err(Box::new(Divergence { step, left: l, right: r }));
+        }
+        if l.state != ExecState::Running {
+            return Ok(Agreement::Stopped(step + 1));
+        }
+    }
+    Ok(Agreement::StepLimit)
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub name: String,
+    pub mem: Vec<Word>,
+    pub inputs: Vec<Word>,
+}
+
+impl Case {
+    pub fn new(name: &str, mem: &[Word], inputs: &[Word]) -> Self {
+        Case { name: name.to_string(), mem: mem.to_vec(), inputs: inputs.to_vec() }
+    }
+}
+
+pub struct EngineKind {
+    pub name: &'static str,
+    // None if the engine cannot run the case
+    pub build: fn(&Case) -> Option<Box<dyn Engine>>,
+}
+
+pub fn engines() -> Vec<EngineKind> {
+    vec![
+        EngineKind {
+            name: "IntcodeComputer",
+            build: |case| Some(Box::new(engine::computer(case.mem.clone(), &case.inputs))),
+        },
+        EngineKind {
+            name: "IntcodeComputer[day2]",
+            build: |case| SimpleMachine::supports(&case.mem).then(|| {
+                let mut computer = engine::computer(case.mem.clone(), &case.inputs);
+                computer.set_profile(IsaProfile::DAY2);
+                Box::new(computer) as Box<dyn Engine>
+            }),
+        },
+        EngineKind {
+            name: "SimpleMachine",
+            build: |case| SimpleMachine::supports(&case.mem)
+                .then(|| Box::new(SimpleMachine::new(case.mem.clone())) as Box<dyn Engine>),
+        },
+    ]
+}
+
+// the example programs from the puzzle descriptions, as used in the tests
+pub fn examples() -> Vec<Case> {
+    let mut cases = vec![
+        Case::new("day2-example", &[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]),
+        Case::new("day2-small-0", &[1, 0, 0, 0, 99], &[]),
+        Case::new("day2-small-1", &[2, 4, 4, 5, 99, 0], &[]),
+        Case::new("day2-small-2", &[1, 1, 1, 4, 99, 5, 6, 0, 99], &[]),
+        Case::new("day9-quine", &[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99], &[]),
+        Case::new("day9-large-mul", &[1102, 34915192, 34915192, 7, 4, 7, 99, 0], &[]),
+        Case::new("day9-large-out", &[104, 1125899906842624, 99], &[]),
+    ];
+    let compare_8 = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    let jump = [3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+    for input in [7, 8, 9] {
+        cases.push(Case::new(&format!("day5-equal-8-{}", input), &compare_8, &[input]));
+        cases.push(Case::new(&format!("day5-jump-{}", input), &jump, &[input - 8]));
+    }
+    cases
+}
+
+// every file under `dir` that parses as a program, once per input set
+pub fn puzzle_inputs(dir: &Path, input_sets: &[&[Word]]) -> io::Result<Vec<Case>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<io::Result<_>>()?;
+    paths.sort();
+    let mut cases = Vec::new();
+    for path in paths {
+        let program = match Program::from_file(&path) {
+            Ok(program) if program.len() > 1 => program,
+            Err(LoadError::Io(e)) if e.kind() != io::ErrorKind::InvalidData => return Err(e),
+            _ => continue,
+        };
+        for inputs in input_sets {
+            cases.push(Case::new(&format!("{}{:?}", program.name.as_deref().unwrap_or_default(), inputs),
+                                 &program.words, inputs));
+        }
+    }
+    Ok(cases)
+}
+
+#[derive(Debug)]
+pub struct CaseReport {
+    pub case: String,
+    pub left: &'static str,
+    pub right: &'static str,
+    pub result: Result<Agreement, Box<Divergence>>,
+}
+
+// run every case on every pair of engines that support it
+pub fn run_corpus(cases: &[Case], engines: &[EngineKind], max_steps: usize) -> Vec<CaseReport> {
+    let mut reports = Vec::new();
+    for case in cases {
+        for (i, left) in engines.iter().enumerate() {
+            for right in &engines[i + 1..] {
+                let (Some(mut l), Some(mut r)) = ((left.build)(case), (right.build)(case)) else { continue };
+                reports.push(CaseReport {
+                    case: case.name.clone(),
+                    left: left.name,
+                    right: right.name,
+                    result: lockstep(l.as_mut(), r.as_mut(), max_steps),
+                });
+            }
+        }
+    }
+    reports
+}
+
+#[test]
+fn test_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("input");
+    let mut cases = examples();
+    cases.extend(puzzle_inputs(&dir, &[&[], &[1], &[5]]).unwrap());
+    let reports = run_corpus(&cases, &engines(), 1_000_000);
+    assert!(reports.iter().any(|r| r.case.starts_with('2')));
+    for report in reports {
+        assert!(report.result.is_ok(), "{}: {}", report.case, report.result.unwrap_err());
+    }
+
+    let mut good = engine::computer(vec![1, 0, 0, 0, 99], &[]);
+    let mut bad = engine::computer(vec![2, 0, 0, 0, 99], &[]);
+    let divergence = lockstep(&mut good, &mut bad, 100).unwrap_err();
+    assert_eq!((divergence.step, divergence.kind()), (0, "memory write"));
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::isa::Fault;
+use super::io::{BufferInput, BufferOutput};
+
+// what a single instruction did, used to compare interpreters step by step
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepEvent {
+    // pc of the executed instruction
+    pub pc: usize,
+    pub writes: Vec<(usize, Word)>,
+    pub output: Option<Word>,
+    pub state: ExecState,
+}
+
+pub trait Engine {
+    fn name(&self) -> &str;
+    fn pc(&self) -> usize;
+    fn step(&mut self) -> StepEvent;
+}
+
+pub type Computer = IntcodeComputer<BufferInput, BufferOutput>;
+
+pub fn computer(mem: Vec<Word>, inputs: &[Word]) -> Computer {
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(inputs), BufferOutput::default());
+    computer.record_writes();
+    computer
+}
+
+impl Engine for Computer {
+    fn name(&self) -> &str {
+        "IntcodeComputer"
+    }
+
+    fn pc(&self) -> usize {
+        IntcodeComputer::pc(self)
+    }
+
+    fn step(&mut self) -> StepEvent {
+        let pc = IntcodeComputer::pc(self);
+        let outputs = self.output_ref().len();
+        let state = IntcodeComputer::step(self);
+        StepEvent {
+            pc,
+            writes: self.take_writes(),
+            output: self.output_ref().deref().get(outputs).copied(),
+            state,
+        }
+    }
+}
+
+
+// interpreter of the first dialect: only Add, Multiply and Halt, position mode only.
+// any other opcode stops the machine
+pub struct SimpleMachine {
+    // sparse like IntcodeComputer's, so that a far write does not allocate up to it
+    mem: BTreeMap<usize, Word>,
+    cursor: usize,
+    state: ExecState,
+}
+
+impl SimpleMachine {
+    pub fn new(mem: Vec<Word>) -> Self {
+        SimpleMachine { mem: mem.into_iter().enumerate().collect(), cursor: 0, state: ExecState::Running }
+    }
+
+    // the program only uses this dialect up to its first Halt
+    pub fn supports(mem: &[Word]) -> bool {
+        let mut pc = 0;
+        while let Some(op) = mem.get(pc) {
+            match op {
+                1 | 2 => pc += 4,
+                99 => return true,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    pub fn mem(&self, addr: usize) -> Word {
+        self.mem.get(&addr).copied().unwrap_or(0)
+    }
+
+    // the operand at `offset` as an address, faulting below 0 like IntcodeComputer
+    fn value_addr(&self, offset: usize) -> Result<usize, Fault> {
+        match self.mem(self.cursor + offset) {
+            addr if addr < 0 => Err(Fault::BadAddress { pc: self.cursor, addr }),
+            addr => Ok(addr as usize),
+        }
+    }
+
+    fn execute(&mut self, op: Word) -> Result<(usize, Word), Fault> {
+        let (a, b, dst) = (self.value_addr(1)?, self.value_addr(2)?, self.value_addr(3)?);
+        let (a, b) = (self.mem(a), self.mem(b));
+        let val = if op == 1 { a + b } else { a * b };
+        self.mem.insert(dst, val);
+        Ok((dst, val))
+    }
+
+    pub fn run(&mut self) -> Word {
+        while self.step().state == ExecState::Running {}
+        self.mem(0)
+    }
+}
+
+impl Engine for SimpleMachine {
+    fn name(&self) -> &str {
+        "SimpleMachine"
+    }
+
+    fn pc(&self) -> usize {
+        self.cursor
+    }
+
+    fn step(&mut self) -> StepEvent {
+        let pc = self.cursor;
+        let mut writes = Vec::new();
+        let op = self.mem(pc);
+        if self.state == ExecState::Running {
+            self.state = match op {
+                1 | 2 => match self.execute(op) {
+                    Ok(write) => {
+                        writes.push(write);
+                        self.cursor += 4;
+                        ExecState::Running
+                    },
+                    Err(fault) => ExecState::Faulted(fault),
+                },
+                _ => ExecState::Halted,
+            };
+        }
+        StepEvent { pc, writes, output: None, state: self.state }
+    }
+}
+
+#[test]
+fn test_simple_machine() {
+    let mut machine = SimpleMachine::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+    assert_eq!(machine.run(), 3500);
+
+    // a negative address faults in both interpreters instead of growing memory
+    let prog = vec![1, 0, 0, -1, 99];
+    let fault = ExecState::Faulted(Fault::BadAddress { pc: 0, addr: -1 });
+    assert_eq!(SimpleMachine::new(prog.clone()).step().state, fault);
+    assert_eq!(Engine::step(&mut computer(prog, &[])).state, fault);
+
+    let mut far = SimpleMachine::new(vec![1, 0, 0, Word::MAX, 99]);
+    assert_eq!(far.step().writes, vec![(Word::MAX as usize, 2)]);
+}
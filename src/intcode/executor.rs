@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+type Task<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+// minimal single-threaded executor. tasks are polled in spawn order and then in wake order,
+// so a set of machines always interleaves the same way
+#[derive(Default)]
+pub struct LocalExecutor<'a> {
+    tasks: Vec<Option<Task<'a>>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl<'a> LocalExecutor<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F: Future<Output = ()> + 'a>(&mut self, future: F) {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(future)));
+    }
+
+    // run until every task finishes. returns the number of tasks that are still pending
+    // with nothing left to wake them, i.e. deadlocked
+    pub fn run(&mut self) -> usize {
+        loop {
+            let next = self.ready.lock().unwrap().pop_front();
+            let Some(id) = next else { break };
+            let Some(task) = self.tasks[id].as_mut() else { continue };
+            let waker = Waker::from(Arc::new(TaskWaker { id, ready: self.ready.clone() }));
+            if let Poll::Ready(()) = task.as_mut().poll(&mut Context::from_waker(&waker)) {
+                self.tasks[id] = None;
+            }
+        }
+        self.tasks.iter().filter(|t| t.is_some()).count()
+    }
+}
@@ -0,0 +1,109 @@
+use super::inst::{Parameter, Word};
+
+// handler of an extension opcode, see `IntcodeComputer::register_extension`
+pub trait HostCall {
+    // number of operands following the opcode
+    fn arity(&self) -> usize;
+    fn call(&mut self, args: &mut HostArgs<'_>);
+}
+
+pub(super) trait MachineAccess {
+    fn read_param(&mut self, param: Parameter) -> Word;
+    fn write_param(&mut self, param: Parameter, val: Word);
+    fn read_mem(&mut self, addr: usize) -> Word;
+    fn write_mem(&mut self, addr: usize, val: Word);
+}
+
+// operands of the current instruction, decoded with their parameter modes
+pub struct HostArgs<'a> {
+    params: &'a [Parameter],
+    machine: &'a mut dyn MachineAccess,
+}
+
+impl<'a> HostArgs<'a> {
+    pub(super) fn new(params: &'a [Parameter], machine: &'a mut dyn MachineAccess) -> Self {
+        HostArgs { params, machine }
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn get(&mut self, idx: usize) -> Word {
+        self.machine.read_param(self.params[idx])
+    }
+
+    // panics if the operand is in immediate mode, like the built-in instructions
+    pub fn set(&mut self, idx: usize, val: Word) {
+        self.machine.write_param(self.params[idx], val)
+    }
+
+    pub fn read_mem(&mut self, addr: usize) -> Word {
+        self.machine.read_mem(addr)
+    }
+
+    pub fn write_mem(&mut self, addr: usize, val: Word) {
+        self.machine.write_mem(addr, val)
+    }
+}
+
+
+// `c = f(a, b)` with the same operand layout as Add and Multiply
+pub struct BinaryOp(pub fn(Word, Word) -> Word);
+
+impl HostCall for BinaryOp {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn call(&mut self, args: &mut HostArgs<'_>) {
+        let val = (self.0)(args.get(0), args.get(1));
+        args.set(2, val);
+    }
+}
+
+// a closure taking the given number of operands
+pub struct HostFn<F>(pub usize, pub F);
+
+impl<F: FnMut(&mut HostArgs<'_>)> HostCall for HostFn<F> {
+    fn arity(&self) -> usize {
+        self.0
+    }
+
+    fn call(&mut self, args: &mut HostArgs<'_>) {
+        (self.1)(args)
+    }
+}
+
+#[test]
+fn test_extension_opcodes() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+
+    // 20: divide, 21: remainder, 22: output the sum of a memory range
+    let prog = vec![1120, 17, 5, 30, 1121, 17, 5, 31, 1122, 30, 31, 4, 30, 4, 31, 4, 40, 99];
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default());
+    computer.set_profile(super::isa::IsaProfile::DAY9);
+    computer.register_extension(20, BinaryOp(|a, b| a / b));
+    computer.register_extension(21, BinaryOp(|a, b| a % b));
+    computer.register_extension(22, HostFn(2, |args: &mut HostArgs<'_>| {
+        let sum = (args.get(0)..args.get(1) + 1).map(|addr| args.read_mem(addr as usize)).sum();
+        args.write_mem(40, sum);
+    }));
+    computer.run_until_finish();
+    assert_eq!(&**computer.output_ref(), &[3, 2, 5]);
+}
+
+#[test]
+#[should_panic(expected = "at most 3 are supported")]
+fn test_extension_arity() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+
+    let mut computer = IntcodeComputer::new(vec![99], BufferInput::new(&[]), BufferOutput::default());
+    computer.register_extension(20, HostFn(19, |_: &mut HostArgs<'_>| ()));
+}
@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
+
+use super::inst::{Cond, Instruction, Operation, Parameter, Word};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Fingerprint {
+    // how often each opcode occurs among the reachable instructions
+    pub histogram: BTreeMap<Word, usize>,
+    // shape of the control flow graph reachable from address 0. inputs of the same puzzle
+    // differ in these, so a family allows a range of them
+    pub blocks: usize,
+    pub edges: usize,
+    // jumps through memory, which are not followed
+    pub indirect: usize,
+}
+
+impl Fingerprint {
+    // walk the instructions reachable from 0 without running them
+    pub fn of(mem: &[Word]) -> Fingerprint {
+        let mut histogram = BTreeMap::new();
+        let mut leaders: BTreeSet<usize> = BTreeSet::from([0]);
+        let mut edges: BTreeSet<(usize, usize)> = BTreeSet::new();
+        let mut seen: BTreeSet<usize> = BTreeSet::new();
+        let mut indirect = 0;
+        let mut pending = vec![0];
+        while let Some(pc) = pending.pop() {
+            if !seen.insert(pc) {
+                continue;
+            }
+            let Some(inst) = Instruction::decode(mem, pc) else { continue };
+            *histogram.entry(inst.op.opcode()).or_insert(0) += 1;
+            let next = pc + inst.op.instruction_len();
+            let (taken, falls) = match inst.jump_cond() {
+                Some(Cond::Always) => (true, false),
+                Some(Cond::Never) => (false, true),
+                Some(Cond::Test(..)) => (true, true),
+                None => (false, inst.op != Operation::Halt),
+            };
+            if taken {
+                match inst.params[1] {
+                    Parameter::Immediate(target) if target >= 0 => {
+                        leaders.insert(target as usize);
+                        edges.insert((pc, target as usize));
+                        pending.push(target as usize);
+                    },
+                    _ => indirect += 1,
+                }
+            }
+            if falls {
+                if inst.jump_cond().is_some() {
+                    leaders.insert(next);
+                    edges.insert((pc, next));
+                }
+                pending.push(next);
+            }
+        }
+        let blocks = leaders.intersection(&seen).count();
+        Fingerprint { histogram, blocks, edges: edges.len(), indirect }
+    }
+
+    // percentage of the reachable instructions with this opcode
+    pub fn share(&self, opcode: Word) -> usize {
+        let total: usize = self.histogram.values().sum();
+        self.histogram.get(&opcode).map_or(0, |n| n * 100 / total)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Family {
+    // day 2, the noun and verb at 1 and 2 are patched before running
+    GravityAssist,
+    // day 5
+    Diagnostic,
+    // day 7
+    Amplifier,
+    // day 9
+    Boost,
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Family::GravityAssist => "gravity assist program",
+            Family::Diagnostic => "TEST diagnostic program",
+            Family::Amplifier => "amplifier controller software",
+            Family::Boost => "BOOST program",
+        };
+        f.write_str(name)
+    }
+}
+
+// what every input of a puzzle has in common. the ranges leave room for the inputs
+// of other players, which differ in their constants and in the code between the scaffolding
+struct Signature {
+    family: Family,
+    // the scaffolding at address 0. None matches any word
+    prologue: &'static [Option<Word>],
+    // the opcodes the reachable code may use
+    opcodes: &'static [Word],
+    // percentage of the reachable instructions for the opcodes that set the family apart
+    shares: &'static [(Word, Range<usize>)],
+    blocks: Range<usize>,
+    edges: Range<usize>,
+    indirect: Range<usize>,
+}
+
+impl Signature {
+    fn matches(&self, mem: &[Word], fingerprint: &Fingerprint) -> bool {
+        mem.len() >= self.prologue.len()
+            && self.prologue.iter().zip(mem).all(|(p, w)| p.is_none_or(|p| p == *w))
+            && fingerprint.histogram.keys().all(|op| self.opcodes.contains(op))
+            && self.shares.iter().all(|(op, range)| range.contains(&fingerprint.share(*op)))
+            && self.blocks.contains(&fingerprint.blocks)
+            && self.edges.contains(&fingerprint.edges)
+            && self.indirect.contains(&fingerprint.indirect)
+    }
+}
+
+const SIGNATURES: &[Signature] = &[
+    // straight-line arithmetic
+    Signature {
+        family: Family::GravityAssist,
+        prologue: &[Some(1), None, None, Some(3), Some(1), Some(1), Some(2), Some(3), Some(1), Some(3), Some(4), Some(3)],
+        opcodes: &[1, 2, 99],
+        shares: &[(1, 40..95), (2, 5..60)],
+        blocks: 1..2,
+        edges: 0..1,
+        indirect: 0..1,
+    },
+    // the code patches the opcode at 6 before the walk can get past it
+    Signature {
+        family: Family::Diagnostic,
+        prologue: &[Some(3), Some(225), Some(1), Some(225), Some(6), Some(6), Some(1100), Some(1), Some(238), Some(225),
+                    Some(104), Some(0)],
+        opcodes: &[1, 2, 3, 4, 5, 6, 7, 8, 99],
+        shares: &[(3, 25..101)],
+        blocks: 1..2,
+        edges: 0..1,
+        indirect: 0..1,
+    },
+    // dispatches on the phase setting through a jump to memory
+    Signature {
+        family: Family::Amplifier,
+        prologue: &[Some(3), Some(8), Some(1001), Some(8), Some(10), Some(8), Some(105), Some(1), Some(0), Some(0)],
+        opcodes: &[1, 2, 3, 4, 5, 6, 7, 8, 99],
+        shares: &[(5, 20..101)],
+        blocks: 1..2,
+        edges: 0..1,
+        indirect: 1..2,
+    },
+    // a self-test of many small blocks, with relative base adjustments throughout
+    Signature {
+        family: Family::Boost,
+        prologue: &[Some(1102), Some(34463338), Some(34463338), Some(63), Some(1007), Some(63), Some(34463338), Some(63),
+                    Some(1005), Some(63), Some(53)],
+        opcodes: &[1, 2, 3, 4, 5, 6, 7, 8, 9, 99],
+        shares: &[(9, 5..30)],
+        blocks: 10..100,
+        edges: 10..100,
+        indirect: 1..10,
+    },
+];
+
+// the known family whose signature `mem` matches
+pub fn classify(mem: &[Word]) -> Option<Family> {
+    let fingerprint = Fingerprint::of(mem);
+    SIGNATURES.iter().find(|s| s.matches(mem, &fingerprint)).map(|s| s.family)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WrongProgram {
+    pub expected: Family,
+    pub found: Option<Family>,
+}
+
+impl fmt::Display for WrongProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.found {
+            Some(found) => write!(f, "expected the {}, but this is the {}", self.expected, found),
+            None => write!(f, "expected the {}, but this is not a known program", self.expected),
+        }
+    }
+}
+
+impl std::error::Error for WrongProgram {}
+
+// for solvers, to refuse the input of another puzzle
+pub fn check(mem: &[Word], expected: Family) -> Result<(), WrongProgram> {
+    match classify(mem) {
+        Some(found) if found == expected => Ok(()),
+        found => Err(WrongProgram { expected, found }),
+    }
+}
+
+#[test]
+fn test_classify() {
+    let parse = |input: &str| super::program::Program::parse(input).unwrap().words;
+    let inputs = [
+        (include_str!("../../input/2"), Family::GravityAssist),
+        (include_str!("../../input/5"), Family::Diagnostic),
+        (include_str!("../../input/7"), Family::Amplifier),
+        (include_str!("../../input/9"), Family::Boost),
+    ];
+    for (input, family) in inputs {
+        assert_eq!(classify(&parse(input)), Some(family));
+    }
+    assert_eq!(classify(&[104, 1, 99]), None);
+
+    let boost = parse(include_str!("../../input/9"));
+    let fingerprint = Fingerprint::of(&boost);
+    assert!(fingerprint.blocks > 1 && fingerprint.indirect > 0);
+    // data constants do not change the fingerprint
+    let mut patched = boost.clone();
+    patched[1] += 1;
+    assert_eq!(Fingerprint::of(&patched), fingerprint);
+    assert_eq!(fingerprint.share(9), 12);
+    // the BOOST scaffolding without the self-test behind it
+    let mut stub = boost[..11].to_vec();
+    stub.push(99);
+    assert_eq!(classify(&stub), None);
+    // a gravity assist program that loops
+    let mut gravity = parse(include_str!("../../input/2"));
+    gravity.splice(12..12, [1105, 1, 0]);
+    assert_eq!(classify(&gravity), None);
+
+    let err = check(&boost, Family::Diagnostic).unwrap_err();
+    assert_eq!(err.to_string(), "expected the TEST diagnostic program, but this is the BOOST program");
+}
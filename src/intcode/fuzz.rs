@@ -0,0 +1,307 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::computer::IntcodeComputer;
+use super::device::Random;
+use super::inst::{Instruction, Word};
+use super::io::{BufferInput, BufferOutput};
+use super::observer::Observer;
+use super::program::{LoadError, Program};
+use super::sweep::{run_bounded, Verdict};
+
+// the addresses of executed instructions
+#[derive(Default, Debug)]
+pub struct Coverage {
+    pub pcs: BTreeSet<usize>,
+}
+
+impl Observer for Coverage {
+    fn on_fetch(&mut self, pc: usize, _inst: &Instruction) {
+        self.pcs.insert(pc);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Case {
+    pub program: Vec<Word>,
+    pub inputs: Vec<Word>,
+    pub verdict: Verdict,
+}
+
+impl Case {
+    // a text file with the program on the first line and the inputs on the second
+    pub fn save<P: AsRef<Path>>(&self, dir: P, name: &str) -> io::Result<PathBuf> {
+        let join = |words: &[Word]| words.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(",");
+        let path = dir.as_ref().join(format!("{}.case", name));
+        fs::write(&path, format!("{}\n{}\n# {:?}\n", join(&self.program), join(&self.inputs), self.verdict))?;
+        Ok(path)
+    }
+
+    // load a saved case and run it again, so that the verdict is the current one
+    pub fn load<P: AsRef<Path>>(path: P, max_steps: usize) -> Result<Case, LoadError> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        let program = Program::parse(lines.next().unwrap_or(""))?.words;
+        let inputs = Program::parse(lines.next().unwrap_or(""))?.words;
+        let verdict = execute(&program, &inputs, max_steps).0;
+        Ok(Case { program, inputs, verdict })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub seed: u64,
+    pub iterations: usize,
+    pub max_steps: usize,
+    pub max_inputs: usize,
+    // range of generated values, besides a few extremes. must not be empty
+    pub values: Range<Word>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { seed: 1, iterations: 1000, max_steps: 100_000, max_inputs: 16, values: -10..100 }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Report {
+    // the cases that first reached new code, minimized
+    pub corpus: Vec<Case>,
+    // one minimized case per distinct crash
+    pub crashes: Vec<Case>,
+    pub coverage: BTreeSet<usize>,
+}
+
+impl Report {
+    // save every case under `dir` as `{prefix}-crash-N.case` and `{prefix}-corpus-N.case`,
+    // as regression fixtures for `regressions`
+    pub fn save<P: AsRef<Path>>(&self, dir: P, prefix: &str) -> io::Result<Vec<PathBuf>> {
+        fs::create_dir_all(&dir)?;
+        let crashes = self.crashes.iter().enumerate().map(|(i, c)| c.save(&dir, &format!("{}-crash-{}", prefix, i)));
+        let corpus = self.corpus.iter().enumerate().map(|(i, c)| c.save(&dir, &format!("{}-corpus-{}", prefix, i)));
+        crashes.chain(corpus).collect()
+    }
+}
+
+// run `program` for at most `max_steps` steps, catching panics
+pub fn execute(program: &[Word], inputs: &[Word], max_steps: usize) -> (Verdict, BTreeSet<usize>) {
+    let mut computer = IntcodeComputer::new(program.to_vec(), BufferInput::new(inputs), BufferOutput::default())
+        .with_observer(Coverage::default());
+    let verdict = run_bounded(&mut computer, max_steps);
+    (verdict, std::mem::take(&mut computer.observer_mut().pcs))
+}
+
+// drop words, last first, then replace the rest with 0 or 1, while `keep` holds
+fn minimize(words: &[Word], keep: impl Fn(&[Word]) -> bool) -> Vec<Word> {
+    let mut best = words.to_vec();
+    for i in (0..best.len()).rev() {
+        let mut candidate = best.clone();
+        candidate.remove(i);
+        if keep(&candidate) {
+            best = candidate;
+        }
+    }
+    for i in 0..best.len() {
+        for simpler in [0, 1] {
+            let mut candidate = best.clone();
+            candidate[i] = simpler;
+            if best[i] != simpler && keep(&candidate) {
+                best = candidate;
+                break;
+            }
+        }
+    }
+    best
+}
+
+struct Generator {
+    rng: Random,
+    values: Range<Word>,
+}
+
+impl Generator {
+    // panics on an empty or reversed range of values
+    fn new(config: &Config) -> Self {
+        assert!(config.values.start < config.values.end, "empty range of values {:?}", config.values);
+        Generator { rng: Random::new(config.seed), values: config.values.clone() }
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.below_u64(n as u64) as usize
+    }
+
+    fn below_u64(&mut self, n: u64) -> u64 {
+        self.rng.next_word() as u64 % n.max(1)
+    }
+
+    fn value(&mut self) -> Word {
+        const EXTREMES: [Word; 5] = [0, 1, -1, Word::MAX, Word::MIN];
+        match self.below(8) {
+            0 => EXTREMES[self.below(EXTREMES.len())],
+            // the width of a range can exceed Word::MAX, the offset then wraps into place
+            _ => {
+                let width = self.values.end.abs_diff(self.values.start);
+                self.values.start.wrapping_add(self.below_u64(width) as Word)
+            },
+        }
+    }
+
+    fn mutate(&mut self, inputs: &[Word], max_inputs: usize) -> Vec<Word> {
+        let mut inputs = inputs.to_vec();
+        for _ in 0..1 + self.below(3) {
+            let i = self.below(inputs.len());
+            match self.below(4) {
+                0 if !inputs.is_empty() => inputs[i] = self.value(),
+                1 if !inputs.is_empty() => inputs[i] = inputs[i].wrapping_add([-1, 1][self.below(2)]),
+                2 if !inputs.is_empty() => { inputs.remove(i); },
+                _ if inputs.len() < max_inputs => {
+                    let value = self.value();
+                    inputs.insert(self.below(inputs.len() + 1), value);
+                },
+                _ => (),
+            }
+        }
+        inputs
+    }
+
+    // mostly well-formed instructions, with operands near the program
+    fn program(&mut self) -> Vec<Word> {
+        const OPCODES: [Word; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+        let len = 1 + self.below(32);
+        (0..len).map(|_| match self.below(3) {
+            0 => {
+                let modes = self.below(3) + 10 * self.below(3) + 100 * self.below(3);
+                modes as Word * 100 + OPCODES[self.below(OPCODES.len())]
+            },
+            1 => self.below(len + 8) as Word,
+            _ => self.value(),
+        }).collect()
+    }
+}
+
+// mutate input sequences for `program`, keeping those that execute new instructions.
+// starts from `seeds`, or from no input at all
+pub fn fuzz_inputs(program: &[Word], seeds: &[Vec<Word>], config: &Config) -> Report {
+    let mut generator = Generator::new(config);
+    let mut report = Report::default();
+    let mut pending: Vec<Vec<Word>> = match seeds.is_empty() {
+        true => vec![Vec::new()],
+        false => seeds.to_vec(),
+    };
+    let run = |inputs: &[Word]| execute(program, inputs, config.max_steps);
+    for _ in 0..config.iterations + pending.len() {
+        let inputs = match pending.pop() {
+            Some(inputs) => inputs,
+            None => {
+                let parent = match report.corpus.len() {
+                    0 => &[][..],
+                    len => &report.corpus[generator.below(len)].inputs,
+                };
+                generator.mutate(parent, config.max_inputs)
+            },
+        };
+        let (verdict, pcs) = run(&inputs);
+        if verdict.is_crash() {
+            if !report.crashes.iter().any(|c| c.verdict == verdict) {
+                let inputs = minimize(&inputs, |i| run(i).0 == verdict);
+                report.crashes.push(Case { program: program.to_vec(), inputs, verdict });
+            }
+        } else if !pcs.is_subset(&report.coverage) {
+            let new: BTreeSet<usize> = pcs.difference(&report.coverage).copied().collect();
+            let inputs = minimize(&inputs, |i| run(i).1.is_superset(&new));
+            report.corpus.push(Case { program: program.to_vec(), inputs, verdict });
+        }
+        report.coverage.extend(pcs);
+    }
+    report
+}
+
+// run random programs on random inputs, collecting the distinct panics of the interpreter
+pub fn fuzz_interpreter(config: &Config) -> Report {
+    let mut generator = Generator::new(config);
+    let mut report = Report::default();
+    for _ in 0..config.iterations {
+        let program = generator.program();
+        let inputs: Vec<Word> = (0..generator.below(config.max_inputs)).map(|_| generator.value()).collect();
+        let (verdict, pcs) = execute(&program, &inputs, config.max_steps);
+        report.coverage.extend(pcs);
+        if matches!(verdict, Verdict::Panic(_)) && !report.crashes.iter().any(|c| c.verdict == verdict) {
+            let program = minimize(&program, |p| execute(p, &inputs, config.max_steps).0 == verdict);
+            let inputs = minimize(&inputs, |i| execute(&program, i, config.max_steps).0 == verdict);
+            report.crashes.push(Case { program, inputs, verdict });
+        }
+    }
+    report
+}
+
+// the fixtures under `dir` whose verdict today differs from the one they were saved with
+pub fn regressions<P: AsRef<Path>>(dir: P, max_steps: usize) -> Result<Vec<PathBuf>, LoadError> {
+    let mut changed = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "case") {
+            continue;
+        }
+        let recorded = fs::read_to_string(&path)?.lines().nth(2).and_then(|l| l.strip_prefix("# ")).map(str::to_string);
+        let case = Case::load(&path, max_steps)?;
+        if recorded != Some(format!("{:?}", case.verdict)) {
+            changed.push(path);
+        }
+    }
+    changed.sort();
+    Ok(changed)
+}
+
+#[test]
+fn test_fuzz() {
+    // outputs 1 for input 42, writes in immediate mode for input 7, otherwise echoes the input
+    let prog = vec![3, 30, 1008, 30, 42, 31, 1005, 31, 20, 1008, 30, 7, 31, 1005, 31, 24, 4, 30, 99, 0,
+                    104, 1, 99, 0, 11101, 0, 0, 0];
+
+    let config = Config { iterations: 2000, values: 0..50, ..Default::default() };
+    let report = fuzz_inputs(&prog, &[], &config);
+    assert!(report.coverage.contains(&20));
+    assert_eq!(report.crashes.len(), 1);
+    assert_eq!(report.crashes[0].inputs, vec![7]);
+    // deterministic under a seed
+    assert_eq!(fuzz_inputs(&prog, &[], &config).corpus.len(), report.corpus.len());
+
+    let dir = std::env::temp_dir().join(format!("adv2019-fuzz-{}", std::process::id()));
+    let paths = report.save(&dir, "echo").unwrap();
+    assert_eq!(paths.len(), report.crashes.len() + report.corpus.len());
+    assert_eq!(regressions(&dir, config.max_steps).unwrap(), Vec::<PathBuf>::new());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let report = fuzz_interpreter(&Config { iterations: 100, max_steps: 1000, ..Default::default() });
+    assert!(!report.coverage.is_empty());
+    assert!(report.crashes.iter().all(|c| matches!(c.verdict, Verdict::Panic(_))));
+}
+
+#[test]
+fn test_fuzz_fixtures() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/fuzz");
+    assert_eq!(regressions(dir, 1000).unwrap(), Vec::<PathBuf>::new());
+    // a deliberate crash, so that a change in how the interpreter fails shows up here
+    let case = Case::load(format!("{}/immediate-write.case", dir), 1000).unwrap();
+    assert_eq!(case.verdict, Verdict::Panic("Cannot set with immediate param".to_string()));
+}
+
+#[test]
+fn test_generator_ranges() {
+    let config = Config { values: Word::MIN..Word::MAX, ..Default::default() };
+    let mut generator = Generator::new(&config);
+    assert!((0..1000).map(|_| generator.value()).any(|v| v < -1));
+    let mut generator = Generator::new(&Config { values: -3..-2, ..config });
+    assert!((0..1000).all(|_| [-3, 0, 1, -1, Word::MAX, Word::MIN].contains(&generator.value())));
+}
+
+#[test]
+#[should_panic(expected = "empty range of values 10..-10")]
+#[allow(clippy::reversed_empty_ranges)]
+fn test_reversed_values() {
+    fuzz_interpreter(&Config { values: 10..-10, ..Default::default() });
+}
@@ -0,0 +1,362 @@
+use std::collections::BTreeSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::io::{Input, Output};
+use super::observer::Observer;
+
+// memory word i is the 8 bytes at byte address i * 8, little endian. register 0 is the pc,
+// register 1 the relative base, both as byte addresses in 64-bit little endian
+const REGISTERS: usize = 2;
+const WORD_BYTES: usize = 8;
+const MAX_PACKET: usize = 0x1000;
+
+const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target version="1.0">"#,
+    r#"<feature name="org.adv2019.intcode"><reg name="pc" bitsize="64" type="code_ptr"/>"#,
+    r#"<reg name="rb" bitsize="64" type="data_ptr"/></feature></target>"#,
+);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GdbExit {
+    Detached,
+    Killed,
+    Disconnected,
+}
+
+pub struct GdbStub<'a, IN, OUT, OBS> {
+    computer: &'a mut IntcodeComputer<IN, OUT, OBS>,
+    // word addresses
+    breakpoints: BTreeSet<usize>,
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(s, 16).ok()
+}
+
+fn parse_hex_bytes(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok())).collect()
+}
+
+// "addr,len" in hex
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((parse_hex(addr)? as usize, parse_hex(len)? as usize))
+}
+
+fn read_byte(stream: &mut TcpStream) -> io::Result<Option<u8>> {
+    let mut buf = [0u8; 1];
+    match stream.read(&mut buf)? {
+        0 => Ok(None),
+        _ => Ok(Some(buf[0])),
+    }
+}
+
+// the next packet, acknowledged, or "\x03" for an interrupt. None when the client is gone
+fn read_packet(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    loop {
+        match read_byte(stream)? {
+            None => return Ok(None),
+            Some(0x03) => return Ok(Some("\x03".to_string())),
+            Some(b'$') => (),
+            // acks and noise between packets
+            Some(_) => continue,
+        }
+        let mut data = Vec::new();
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(b) => data.push(b),
+            }
+        }
+        let (Some(hi), Some(lo)) = (read_byte(stream)?, read_byte(stream)?) else {
+            return Ok(None);
+        };
+        let sum = std::str::from_utf8(&[hi, lo]).ok().and_then(|s| u8::from_str_radix(s, 16).ok());
+        if sum == Some(checksum(&data)) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+fn send_packet(stream: &mut TcpStream, data: &str) -> io::Result<()> {
+    write!(stream, "${}#{:02x}", data, checksum(data.as_bytes()))?;
+    stream.flush()
+}
+
+// whether the client sent an interrupt while the machine runs. anything else stays
+// in the stream for `read_packet`
+fn interrupted(stream: &mut TcpStream) -> io::Result<bool> {
+    stream.set_nonblocking(true)?;
+    let mut buf = [0u8; 1];
+    let result = match stream.peek(&mut buf) {
+        Ok(1) if buf[0] == 0x03 => stream.read(&mut buf).map(|_| true),
+        Ok(_) => Ok(false),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(e) => Err(e),
+    };
+    stream.set_nonblocking(false)?;
+    result
+}
+
+impl<'a, IN, OUT, OBS> GdbStub<'a, IN, OUT, OBS>
+where IN: Input, OUT: Output, OBS: Observer {
+
+    pub fn new(computer: &'a mut IntcodeComputer<IN, OUT, OBS>) -> Self {
+        GdbStub { computer, breakpoints: BTreeSet::new() }
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    // serve one client until it detaches, kills the session or disconnects.
+    // the machine is left where the client stopped it
+    pub fn serve(&mut self, mut stream: TcpStream) -> io::Result<GdbExit> {
+        while let Some(packet) = read_packet(&mut stream)? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'k') => return Ok(GdbExit::Killed),
+                Some(b'D') => {
+                    send_packet(&mut stream, "OK")?;
+                    return Ok(GdbExit::Detached);
+                },
+                Some(b'c') => self.resume(&mut stream, false)?,
+                Some(b's') => self.resume(&mut stream, true)?,
+                _ => self.handle(&packet),
+            };
+            send_packet(&mut stream, &reply)?;
+        }
+        Ok(GdbExit::Disconnected)
+    }
+
+    fn stop_reply(&self, state: ExecState) -> String {
+        match state {
+            ExecState::Halted => "W00".to_string(),
+            // SIGILL
+            ExecState::Faulted(_) => "S04".to_string(),
+            // SIGTTIN, a read with no input to give it
+            ExecState::WaitingInput => "S15".to_string(),
+            // SIGTRAP
+            _ => "S05".to_string(),
+        }
+    }
+
+    // run until a breakpoint, an interrupt or the machine blocks, or a single step
+    fn resume(&mut self, stream: &mut TcpStream, single: bool) -> io::Result<String> {
+        let mut steps = 0usize;
+        loop {
+            let state = self.computer.step();
+            if state != ExecState::Running || single || self.breakpoints.contains(&self.computer.pc()) {
+                return Ok(self.stop_reply(state));
+            }
+            steps += 1;
+            if steps.is_multiple_of(4096) && interrupted(stream)? {
+                // SIGINT
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    fn register(&self, n: usize) -> Option<u64> {
+        let word = match n {
+            0 => self.computer.pc(),
+            1 => self.computer.relative_base(),
+            _ => return None,
+        };
+        (word as u64).checked_mul(WORD_BYTES as u64)
+    }
+
+    fn set_register(&mut self, n: usize, val: u64) -> bool {
+        let word = (val / WORD_BYTES as u64) as usize;
+        match n {
+            0 => self.computer.set_pc(word),
+            1 => self.computer.set_relative_base(word),
+            _ => return false,
+        }
+        true
+    }
+
+    // None if the range runs past the end of the address space
+    fn read_bytes(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        let end = addr.checked_add(len)?;
+        Some((addr..end).map(|b| self.computer.peek(b / WORD_BYTES).to_le_bytes()[b % WORD_BYTES]).collect())
+    }
+
+    fn write_bytes(&mut self, addr: usize, bytes: &[u8]) -> Option<()> {
+        addr.checked_add(bytes.len())?;
+        for (i, byte) in bytes.iter().enumerate() {
+            let b = addr + i;
+            let mut word = self.computer.peek(b / WORD_BYTES).to_le_bytes();
+            word[b % WORD_BYTES] = *byte;
+            self.computer.poke(b / WORD_BYTES, Word::from_le_bytes(word));
+        }
+        Some(())
+    }
+
+    // the reply to a packet that does not run the machine. empty means unsupported
+    fn handle(&mut self, packet: &str) -> String {
+        const ERROR: &str = "E01";
+        let (cmd, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match cmd {
+            "?" => Some("S05".to_string()),
+            "\x03" => Some("S02".to_string()),
+            "H" => Some("OK".to_string()),
+            "g" => (0..REGISTERS).map(|n| self.register(n)).collect::<Option<Vec<u64>>>()
+                .map(|regs| hex_bytes(&regs.into_iter().flat_map(u64::to_le_bytes).collect::<Vec<u8>>())),
+            "G" => parse_hex_bytes(args).filter(|b| b.len() == REGISTERS * 8).map(|bytes| {
+                for (n, chunk) in bytes.chunks(8).enumerate() {
+                    self.set_register(n, u64::from_le_bytes(chunk.try_into().unwrap()));
+                }
+                "OK".to_string()
+            }),
+            "p" => parse_hex(args).and_then(|n| self.register(n as usize)).map(|v| hex_bytes(&v.to_le_bytes())),
+            "P" => args.split_once('=').and_then(|(n, val)| {
+                let bytes: [u8; 8] = parse_hex_bytes(val)?.try_into().ok()?;
+                self.set_register(parse_hex(n)? as usize, u64::from_le_bytes(bytes)).then(|| "OK".to_string())
+            }),
+            "m" => parse_range(args)
+                .and_then(|(addr, len)| self.read_bytes(addr, len.min(MAX_PACKET / 2)))
+                .map(|bytes| hex_bytes(&bytes)),
+            "M" => args.split_once(':').and_then(|(range, data)| {
+                let (addr, len) = parse_range(range)?;
+                let bytes = parse_hex_bytes(data).filter(|b| b.len() == len)?;
+                self.write_bytes(addr, &bytes)?;
+                Some("OK".to_string())
+            }),
+            "Z" | "z" => match args.split(',').collect::<Vec<_>>()[..] {
+                ["0", addr, _] => parse_hex(addr).map(|addr| {
+                    let addr = addr as usize / WORD_BYTES;
+                    match cmd {
+                        "Z" => self.breakpoints.insert(addr),
+                        _ => self.breakpoints.remove(&addr),
+                    };
+                    "OK".to_string()
+                }),
+                // hardware breakpoints and watchpoints
+                _ => return String::new(),
+            },
+            "q" => return self.query(args),
+            _ => return String::new(),
+        };
+        reply.unwrap_or_else(|| ERROR.to_string())
+    }
+
+    fn query(&self, args: &str) -> String {
+        if args.starts_with("Supported") {
+            return format!("PacketSize={:x};qXfer:features:read+", MAX_PACKET);
+        }
+        if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            let Some((offset, len)) = parse_range(range) else { return "E01".to_string() };
+            let rest = TARGET_XML.get(offset.min(TARGET_XML.len())..).unwrap_or("");
+            return match rest.len() > len {
+                true => format!("m{}", &rest[..len]),
+                false => format!("l{}", rest),
+            };
+        }
+        match args {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+// accept one debugger and let it control `computer`
+pub fn serve_gdb<IN, OUT, OBS>(computer: &mut IntcodeComputer<IN, OUT, OBS>, listener: &TcpListener) -> io::Result<GdbExit>
+where IN: Input, OUT: Output, OBS: Observer {
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(computer).serve(stream)
+}
+
+#[test]
+fn test_serve_gdb() {
+    use super::io::{BufferInput, BufferOutput};
+
+    fn request(stream: &mut TcpStream, packet: &str) -> String {
+        send_packet(stream, packet).unwrap();
+        assert_eq!(read_byte(stream).unwrap(), Some(b'+'));
+        read_packet(stream).unwrap().unwrap()
+    }
+
+    // counts [20] down from 3 to 0, then outputs it
+    let prog = vec![1101, 0, 3, 20, 1001, 20, -1, 20, 1005, 20, 4, 4, 20, 99];
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default());
+        let exit = serve_gdb(&mut computer, &listener).unwrap();
+        (exit, computer.output_ref().to_vec())
+    });
+
+    let mut stream = TcpStream::connect(addr).unwrap();
+    assert!(request(&mut stream, "qSupported:swbreak+").contains("qXfer:features:read+"));
+    assert_eq!(request(&mut stream, "?"), "S05");
+    assert_eq!(request(&mut stream, "s"), "S05");
+    // the pc is a byte address too: word 4 is 0x20
+    assert_eq!(request(&mut stream, "p0"), "2000000000000000");
+    // the loop body at word 4 is byte 0x20
+    assert_eq!(request(&mut stream, "Z0,20,1"), "OK");
+    assert_eq!(request(&mut stream, "c"), "S05");
+    assert_eq!(request(&mut stream, "ma0,8"), "0200000000000000");
+    assert_eq!(request(&mut stream, "Ma0,8:0100000000000000"), "OK");
+    assert_eq!(request(&mut stream, "z0,20,1"), "OK");
+    assert_eq!(request(&mut stream, "c"), "W00");
+    assert_eq!(request(&mut stream, "g"), "68000000000000000000000000000000");
+    assert_eq!(request(&mut stream, "P1=1000000000000000"), "OK");
+    assert_eq!(request(&mut stream, "p1"), "1000000000000000");
+    assert_eq!(request(&mut stream, "mffffffffffffffff,8"), "E01");
+    assert_eq!(request(&mut stream, "Mffffffffffffffff,1:00"), "E01");
+    assert_eq!(request(&mut stream, "vMustReplyEmpty"), "");
+    send_packet(&mut stream, "k").unwrap();
+    assert_eq!(server.join().unwrap(), (GdbExit::Killed, vec![0]));
+
+    // a machine stopped for input is told apart from a breakpoint
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut computer = IntcodeComputer::new(vec![3, 0, 99], BufferInput::new(&[]), BufferOutput::default());
+        serve_gdb(&mut computer, &listener).unwrap()
+    });
+    let mut stream = TcpStream::connect(addr).unwrap();
+    assert_eq!(request(&mut stream, "c"), "S15");
+    send_packet(&mut stream, "k").unwrap();
+    assert_eq!(server.join().unwrap(), GdbExit::Killed);
+}
+
+#[test]
+fn test_interrupted() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    // the server sees the bytes once peek returns
+    let send = |client: &mut TcpStream, server: &TcpStream, bytes: &[u8]| {
+        client.write_all(bytes).unwrap();
+        server.peek(&mut [0u8; 1]).unwrap();
+    };
+
+    // a packet that arrives while the machine runs is kept for the next read
+    send(&mut client, &server, b"$?#3f");
+    assert!(!interrupted(&mut server).unwrap());
+    assert_eq!(read_packet(&mut server).unwrap().as_deref(), Some("?"));
+    send(&mut client, &server, b"\x03");
+    assert!(interrupted(&mut server).unwrap());
+    assert!(!interrupted(&mut server).unwrap());
+}
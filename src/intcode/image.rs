@@ -0,0 +1,236 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::inst::Word;
+use super::program::Program;
+
+// layout, all integers are LEB128 varints unless noted:
+//   magic "ICIM", version: u8, word width in bits: u8, flags: u8
+//   word count, words (zigzag encoded)
+//   if FLAG_SYMBOLS: symbol count, then (address, name length, utf8 name) per symbol
+//   if FLAG_NAME: name length, utf8 name
+//   FNV-1a checksum of all the above: u32 little endian
+pub const MAGIC: &[u8; 4] = b"ICIM";
+pub const VERSION: u8 = 1;
+const WORD_WIDTH: u8 = 64;
+const FLAG_SYMBOLS: u8 = 1;
+const FLAG_NAME: u8 = 2;
+const KNOWN_FLAGS: u8 = FLAG_SYMBOLS | FLAG_NAME;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImageError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnsupportedWordWidth(u8),
+    Truncated,
+    Overflow,
+    InvalidUtf8,
+    UnknownFlags(u8),
+    // bytes left between the last field and the checksum
+    TrailingBytes(usize),
+    Checksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::BadMagic => write!(f, "not a program image"),
+            ImageError::UnsupportedVersion(v) => write!(f, "unsupported image version {}", v),
+            ImageError::UnsupportedWordWidth(w) => write!(f, "unsupported word width {}", w),
+            ImageError::Truncated => write!(f, "truncated image"),
+            ImageError::Overflow => write!(f, "varint overflow"),
+            ImageError::InvalidUtf8 => write!(f, "invalid utf8 string"),
+            ImageError::UnknownFlags(flags) => write!(f, "unknown flags {:#04x}", flags),
+            ImageError::TrailingBytes(n) => write!(f, "{} trailing bytes", n),
+            ImageError::Checksum { expected, actual } =>
+                write!(f, "checksum mismatch: expected {:08x}, got {:08x}", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+fn fnv1a(data: &[u8]) -> u32 {
+    data.iter().fold(0x811c9dc5, |hash, b| (hash ^ *b as u32).wrapping_mul(0x01000193))
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x80 {
+        buf.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+    buf.push(val as u8);
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    put_varint(buf, s.len() as u64);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn zigzag(val: Word) -> u64 {
+    ((val << 1) ^ (val >> 63)) as u64
+}
+
+fn unzigzag(val: u64) -> Word {
+    ((val >> 1) as Word) ^ -((val & 1) as Word)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, ImageError> {
+        let b = *self.data.get(self.pos).ok_or(ImageError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], ImageError> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or(ImageError::Truncated)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, ImageError> {
+        let mut val = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            // the 10th byte holds the top bit only
+            if shift == 63 && b > 1 {
+                return Err(ImageError::Overflow);
+            }
+            val |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(val);
+            }
+        }
+        Err(ImageError::Overflow)
+    }
+
+    fn len(&mut self) -> Result<usize, ImageError> {
+        usize::try_from(self.varint()?).map_err(|_| ImageError::Overflow)
+    }
+
+    fn string(&mut self) -> Result<String, ImageError> {
+        let len = self.len()?;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| ImageError::InvalidUtf8)
+    }
+}
+
+pub fn is_image(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// the source path is not stored
+pub fn encode(program: &Program) -> Vec<u8> {
+    let mut flags = 0;
+    if !program.symbols.is_empty() {
+        flags |= FLAG_SYMBOLS;
+    }
+    if program.name.is_some() {
+        flags |= FLAG_NAME;
+    }
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&[VERSION, WORD_WIDTH, flags]);
+    put_varint(&mut buf, program.words.len() as u64);
+    for word in program.words.iter() {
+        put_varint(&mut buf, zigzag(*word));
+    }
+    if flags & FLAG_SYMBOLS != 0 {
+        put_varint(&mut buf, program.symbols.len() as u64);
+        for (addr, name) in program.symbols.iter() {
+            put_varint(&mut buf, *addr as u64);
+            put_str(&mut buf, name);
+        }
+    }
+    if let Some(name) = program.name.as_ref() {
+        put_str(&mut buf, name);
+    }
+    let checksum = fnv1a(&buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+pub fn decode(data: &[u8]) -> Result<Program, ImageError> {
+    if !is_image(data) {
+        return Err(ImageError::BadMagic);
+    }
+    if data.len() < MAGIC.len() + 3 + 4 {
+        return Err(ImageError::Truncated);
+    }
+    let (body, checksum) = data.split_at(data.len() - 4);
+    let expected = u32::from_le_bytes(checksum.try_into().unwrap());
+    let actual = fnv1a(body);
+    if expected != actual {
+        return Err(ImageError::Checksum { expected, actual });
+    }
+
+    let mut reader = Reader { data: body, pos: MAGIC.len() };
+    match reader.byte()? {
+        VERSION => (),
+        v => return Err(ImageError::UnsupportedVersion(v)),
+    }
+    match reader.byte()? {
+        WORD_WIDTH => (),
+        w => return Err(ImageError::UnsupportedWordWidth(w)),
+    }
+    let flags = reader.byte()?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(ImageError::UnknownFlags(flags));
+    }
+
+    let count = reader.len()?;
+    let mut program = Program::new(Vec::with_capacity(count.min(body.len())));
+    for _ in 0..count {
+        program.words.push(unzigzag(reader.varint()?));
+    }
+    if flags & FLAG_SYMBOLS != 0 {
+        for _ in 0..reader.len()? {
+            let addr = reader.len()?;
+            program.symbols.insert(addr, reader.string()?);
+        }
+    }
+    if flags & FLAG_NAME != 0 {
+        program.name = Some(reader.string()?);
+    }
+    if reader.pos != body.len() {
+        return Err(ImageError::TrailingBytes(body.len() - reader.pos));
+    }
+    Ok(program)
+}
+
+pub fn save<P: AsRef<Path>>(program: &Program, path: P) -> io::Result<()> {
+    fs::write(path, encode(program))
+}
+
+#[test]
+fn test_roundtrip() {
+    let mut program = Program::new(vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0, -1, Word::MIN, Word::MAX])
+        .with_name("day9");
+    program.symbols.insert(7, "result".to_string());
+    let bytes = encode(&program);
+    assert_eq!(decode(&bytes).unwrap(), program);
+
+    let mut corrupted = bytes.clone();
+    corrupted[10] ^= 1;
+    assert!(matches!(decode(&corrupted), Err(ImageError::Checksum { .. })));
+    assert_eq!(decode(b"ICIM\x01").unwrap_err(), ImageError::Truncated);
+    assert_eq!(decode(b"1,2,3,99").unwrap_err(), ImageError::BadMagic);
+
+    // well-formed checksums over malformed bodies
+    let seal = |body: &[u8]| [body, &fnv1a(body).to_le_bytes()].concat();
+    let body = &bytes[..bytes.len() - 4];
+    assert_eq!(decode(&seal(&[body, &[0]].concat())).unwrap_err(), ImageError::TrailingBytes(1));
+    let mut flags = body.to_vec();
+    flags[6] |= 4;
+    assert_eq!(decode(&seal(&flags)).unwrap_err(), ImageError::UnknownFlags(7));
+    let mut wide = b"ICIM\x01\x40\x00\x01".to_vec();
+    wide.extend_from_slice(&[0xff; 9]);
+    assert_eq!(decode(&seal(&[&wide[..], &[0x01]].concat())).unwrap().words, vec![Word::MIN]);
+    assert_eq!(decode(&seal(&[&wide[..], &[0x02]].concat())).unwrap_err(), ImageError::Overflow);
+}
@@ -1,6 +1,6 @@
 pub type Word = i64;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Parameter {
     AbsPosition(usize),
     RelPosition(Word),
@@ -8,6 +8,10 @@ pub enum Parameter {
 }
 
 impl Parameter {
+    pub fn is_valid_mode(mode: i8) -> bool {
+        (0..=2).contains(&mode)
+    }
+
     pub fn new(mode: i8, val: Word) -> Self {
         match mode {
             1 => Parameter::Immediate(val),
@@ -16,13 +20,31 @@ impl Parameter {
             _ => unimplemented!("unsupported mode"),
         }
     }
+
+    pub fn mode(&self) -> i8 {
+        match self {
+            Parameter::AbsPosition(_) => 0,
+            Parameter::Immediate(_) => 1,
+            Parameter::RelPosition(_) => 2,
+        }
+    }
+
+    // the word as written in memory
+    pub fn value(&self) -> Word {
+        match *self {
+            Parameter::AbsPosition(a) => a as Word,
+            Parameter::Immediate(v) | Parameter::RelPosition(v) => v,
+        }
+    }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Operation {
     Add, Multiply, Input, Output,
     JumpIfTrue, JumpIfFalse, LessThan, Equals,
     AdjustRelativeBase,
+    // opcode registered with `IntcodeComputer::register_extension`
+    Extension { opcode: Word, arity: usize },
     Halt,
 }
 
@@ -33,11 +55,45 @@ impl Operation {
             Self::JumpIfTrue | Self::JumpIfFalse => 3,
             Self::Input | Self::Output => 2,
             Self::AdjustRelativeBase => 2,
+            Self::Extension { arity, .. } => arity + 1,
             Self::Halt => 1,
         }
     }
 }
 
+impl Operation {
+    pub fn opcode(&self) -> Word {
+        match self {
+            Self::Add => 1,
+            Self::Multiply => 2,
+            Self::Input => 3,
+            Self::Output => 4,
+            Self::JumpIfTrue => 5,
+            Self::JumpIfFalse => 6,
+            Self::LessThan => 7,
+            Self::Equals => 8,
+            Self::AdjustRelativeBase => 9,
+            Self::Extension { opcode, .. } => *opcode,
+            Self::Halt => 99,
+        }
+    }
+
+    // indices of the parameters the operation reads, and of the one it writes
+    pub fn roles(&self) -> (&'static [usize], Option<usize>) {
+        match self {
+            Self::Add | Self::Multiply | Self::LessThan | Self::Equals => (&[0, 1], Some(2)),
+            Self::Input => (&[], Some(0)),
+            Self::Output | Self::AdjustRelativeBase => (&[0], None),
+            Self::JumpIfTrue | Self::JumpIfFalse => (&[0, 1], None),
+            _ => (&[], None),
+        }
+    }
+
+    pub fn is_builtin_opcode(opcode: i64) -> bool {
+        (1..=9).contains(&opcode) || opcode == 99
+    }
+}
+
 impl From<i64> for Operation {
     fn from(opcode: i64) -> Self {
         match opcode {
@@ -55,8 +111,59 @@ impl From<i64> for Operation {
     }
 }
 
-#[derive(Debug)]
+// whether a jump is taken, as far as its first parameter tells
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cond {
+    Always,
+    Never,
+    // jump when the parameter is non-zero, or zero
+    Test(Parameter, bool),
+}
+
+#[derive(Clone, Debug)]
 pub struct Instruction {
     pub op: Operation,
     pub params: Vec<Parameter>,
 }
+
+impl Instruction {
+    // decode a built-in instruction at `addr`, without a machine. None for unknown
+    // opcodes, invalid modes and instructions running off the end of `mem`
+    pub fn decode(mem: &[Word], addr: usize) -> Option<Instruction> {
+        let word = *mem.get(addr)?;
+        if word < 0 || !Operation::is_builtin_opcode(word % 100) {
+            return None;
+        }
+        let op = Operation::from(word % 100);
+        let mut params = Vec::new();
+        for i in 0..op.instruction_len() - 1 {
+            let mode = ((word / 100 / 10i64.pow(i as u32)) % 10) as i8;
+            if !Parameter::is_valid_mode(mode) {
+                return None;
+            }
+            params.push(Parameter::new(mode, *mem.get(addr + 1 + i)?));
+        }
+        Some(Instruction { op, params })
+    }
+
+    // None if the instruction is not a jump
+    pub fn jump_cond(&self) -> Option<Cond> {
+        let if_true = match self.op {
+            Operation::JumpIfTrue => true,
+            Operation::JumpIfFalse => false,
+            _ => return None,
+        };
+        Some(match self.params[0] {
+            Parameter::Immediate(v) if (v != 0) == if_true => Cond::Always,
+            Parameter::Immediate(_) => Cond::Never,
+            param => Cond::Test(param, if_true),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<Word> {
+        let modes: Word = self.params.iter().rev().fold(0, |acc, p| acc * 10 + p.mode() as Word);
+        std::iter::once(modes * 100 + self.op.opcode())
+            .chain(self.params.iter().map(Parameter::value))
+            .collect()
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, ops::Deref};
+use std::{cell::RefCell, collections::VecDeque, ops::Deref, rc::Rc};
 
 use super::inst::Word;
 
@@ -25,6 +25,91 @@ impl BufferInput {
     pub fn new(vals: &[Word]) -> Self {
         BufferInput { inputs: VecDeque::from(Vec::<Word>::from(vals)) }
     }
+
+    pub fn push(&mut self, val: Word) {
+        self.inputs.push_back(val);
+    }
+}
+
+
+// never blocks: returns `default` when the queue is empty, e.g. -1 for networked programs.
+// `idle_reads` counts the consecutive reads that hit an empty queue
+pub struct PollingInput {
+    inputs: VecDeque<Word>,
+    default: Word,
+    idle_reads: usize,
+}
+
+impl Input for PollingInput {
+    fn read(&mut self) -> Option<Word> {
+        match self.inputs.pop_front() {
+            Some(v) => {
+                self.idle_reads = 0;
+                Some(v)
+            },
+            None => {
+                self.idle_reads += 1;
+                Some(self.default)
+            },
+        }
+    }
+}
+
+impl PollingInput {
+    pub fn new(default: Word) -> Self {
+        PollingInput { inputs: VecDeque::new(), default, idle_reads: 0 }
+    }
+
+    pub fn push(&mut self, val: Word) {
+        self.inputs.push_back(val);
+    }
+
+    pub fn extend(&mut self, vals: &[Word]) {
+        self.inputs.extend(vals);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn idle_reads(&self) -> usize {
+        self.idle_reads
+    }
+
+    // polled the empty queue at least `threshold` times in a row, with nothing queued since
+    pub fn is_idle(&self, threshold: usize) -> bool {
+        self.inputs.is_empty() && self.idle_reads >= threshold
+    }
+}
+
+
+// shared queue for connecting machines on one thread: one machine writes into a clone
+// of the pipe, another one reads from it
+#[derive(Clone, Default)]
+pub struct Pipe {
+    queue: Rc<RefCell<VecDeque<Word>>>,
+}
+
+impl Input for Pipe {
+    fn read(&mut self) -> Option<Word> {
+        self.queue.borrow_mut().pop_front()
+    }
+}
+
+impl Output for Pipe {
+    fn write(&mut self, val: Word) {
+        self.queue.borrow_mut().push_back(val)
+    }
+}
+
+impl Pipe {
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.borrow().is_empty()
+    }
 }
 
 
@@ -46,3 +131,15 @@ impl Deref for BufferOutput {
         self.outputs.deref()
     }
 }
+
+#[test]
+fn test_polling_input() {
+    let mut input = PollingInput::new(-1);
+    assert_eq!(input.read(), Some(-1));
+    assert_eq!(input.read(), Some(-1));
+    assert!(input.is_idle(2));
+    input.push(5);
+    assert!(!input.is_idle(1));
+    assert_eq!(input.read(), Some(5));
+    assert_eq!(input.idle_reads(), 0);
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+use super::inst::Word;
+
+// the opcodes and parameter modes accepted by a dialect of the instruction set
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IsaProfile {
+    pub name: &'static str,
+    opcodes: u128,
+    modes: u8,
+}
+
+const fn opcode_mask(opcodes: &[Word]) -> u128 {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < opcodes.len() {
+        mask |= 1 << opcodes[i];
+        i += 1;
+    }
+    mask
+}
+
+impl IsaProfile {
+    // Add, Multiply and Halt, position mode only
+    pub const DAY2: Self = IsaProfile { name: "day2", opcodes: opcode_mask(&[1, 2, 99]), modes: 0b001 };
+    // adds io, jumps and comparisons, and immediate mode
+    pub const DAY5: Self = IsaProfile { name: "day5", opcodes: opcode_mask(&[1, 2, 3, 4, 5, 6, 7, 8, 99]), modes: 0b011 };
+    // adds the relative base and relative mode
+    pub const DAY9: Self = IsaProfile { name: "day9", opcodes: opcode_mask(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 99]), modes: 0b111 };
+
+    pub fn allows_opcode(&self, opcode: Word) -> bool {
+        (0..128).contains(&opcode) && self.opcodes & (1 << opcode) != 0
+    }
+
+    pub fn allows_mode(&self, mode: i8) -> bool {
+        (0..8).contains(&mode) && self.modes & (1 << mode) != 0
+    }
+}
+
+// why a machine stopped without reaching Halt
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fault {
+    IllegalOpcode { pc: usize, opcode: Word },
+    IllegalMode { pc: usize, mode: i8 },
+    // a write into executed code, under `CodePolicy::Trap`
+    CodeWrite { pc: usize, addr: usize },
+    // a position or relative parameter that resolves below 0
+    BadAddress { pc: usize, addr: Word },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::IllegalOpcode { pc, opcode } => write!(f, "illegal opcode {} at {}", opcode, pc),
+            Fault::IllegalMode { pc, mode } => write!(f, "illegal parameter mode {} at {}", mode, pc),
+            Fault::CodeWrite { pc, addr } => write!(f, "write to code at {} from {}", addr, pc),
+            Fault::BadAddress { pc, addr } => write!(f, "bad address {} at {}", addr, pc),
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+#[test]
+fn test_profile() {
+    use super::computer::{ExecState, IntcodeComputer};
+    use super::io::{BufferInput, BufferOutput};
+
+    let run = |prog: Vec<Word>, profile: IsaProfile| {
+        let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[1]), BufferOutput::default());
+        computer.set_profile(profile);
+        computer.run_until_blocked()
+    };
+    assert_eq!(run(vec![1, 0, 0, 0, 99], IsaProfile::DAY2), ExecState::Halted);
+    assert_eq!(run(vec![1, 0, 0, 0, 3, 0, 99], IsaProfile::DAY2),
+               ExecState::Faulted(Fault::IllegalOpcode { pc: 4, opcode: 3 }));
+    assert_eq!(run(vec![1101, 0, 0, 0, 99], IsaProfile::DAY2),
+               ExecState::Faulted(Fault::IllegalMode { pc: 0, mode: 1 }));
+    assert_eq!(run(vec![1101, 0, 0, 0, 3, 0, 99], IsaProfile::DAY5), ExecState::Halted);
+    assert_eq!(run(vec![109, 1, 99], IsaProfile::DAY5),
+               ExecState::Faulted(Fault::IllegalOpcode { pc: 0, opcode: 9 }));
+    assert_eq!(run(vec![109, 1, 2201, 0, 0, 0, 99], IsaProfile::DAY9), ExecState::Halted);
+}
@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use super::inst::{Operation, Word};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Finding {
+    // `Operation::from` would silently halt
+    UnknownOpcode { opcode: Word },
+    InvalidMode { param: usize, mode: Word },
+    ImmediateWrite { param: usize },
+    JumpOutsideImage { target: Word },
+    NegativeAddress { param: usize, addr: Word },
+    // the instruction runs past the end of the image
+    Truncated,
+    // a jump lands inside the instruction at `inst`
+    OverlappingTarget { inst: usize },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    pub addr: usize,
+    pub severity: Severity,
+    pub finding: Finding,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UnknownOpcode { opcode } => write!(f, "unknown opcode {}", opcode),
+            Finding::InvalidMode { param, mode } => write!(f, "parameter {} has invalid mode {}", param, mode),
+            Finding::ImmediateWrite { param } => write!(f, "parameter {} is written in immediate mode", param),
+            Finding::JumpOutsideImage { target } => write!(f, "jump to {} outside the image", target),
+            Finding::NegativeAddress { param, addr } => write!(f, "parameter {} addresses {}", param, addr),
+            Finding::Truncated => write!(f, "instruction runs past the end of the image"),
+            Finding::OverlappingTarget { inst } => write!(f, "jump target inside the instruction at {}", inst),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", self.addr, severity, self.finding)
+    }
+}
+
+// check the instructions reachable from address 0 without running them. jumps through
+// memory are not followed. findings on cells the program writes itself are only warnings,
+// since the code there may differ at runtime
+pub fn lint(mem: &[Word]) -> Vec<Diagnostic> {
+    let mut findings: Vec<(usize, Finding)> = Vec::new();
+    let mut insts: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut targets: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut written: BTreeSet<usize> = BTreeSet::new();
+    let mut pending = vec![0];
+    while let Some(pc) = pending.pop() {
+        if insts.contains_key(&pc) || pc >= mem.len() {
+            continue;
+        }
+        let word = mem[pc];
+        let opcode = word % 100;
+        if word < 0 || !Operation::is_builtin_opcode(opcode) {
+            findings.push((pc, Finding::UnknownOpcode { opcode }));
+            continue;
+        }
+        let op = Operation::from(opcode);
+        let len = op.instruction_len();
+        insts.insert(pc, len);
+        if pc + len > mem.len() {
+            findings.push((pc, Finding::Truncated));
+            continue;
+        }
+        let (_, write) = op.roles();
+        let mut valid = true;
+        for i in 0..len - 1 {
+            let mode = (word / 100 / 10i64.pow(i as u32)) % 10;
+            let val = mem[pc + 1 + i];
+            match mode {
+                0 if val < 0 => findings.push((pc, Finding::NegativeAddress { param: i, addr: val })),
+                0 if write == Some(i) => { written.insert(val as usize); },
+                1 if write == Some(i) => findings.push((pc, Finding::ImmediateWrite { param: i })),
+                0..=2 => (),
+                _ => {
+                    findings.push((pc, Finding::InvalidMode { param: i, mode }));
+                    valid = false;
+                },
+            }
+        }
+        if !valid || op == Operation::Halt {
+            continue;
+        }
+        let next = pc + len;
+        let always = match op {
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let modes = word / 100;
+                if modes / 10 % 10 == 1 {
+                    match mem[pc + 2] {
+                        target if target < 0 || target as usize >= mem.len() =>
+                            findings.push((pc, Finding::JumpOutsideImage { target })),
+                        target => {
+                            targets.insert(target as usize, pc);
+                            pending.push(target as usize);
+                        },
+                    }
+                }
+                let cond = mem[pc + 1];
+                modes % 10 == 1 && (cond != 0) == (op == Operation::JumpIfTrue)
+            },
+            _ => false,
+        };
+        if !always {
+            pending.push(next);
+        }
+    }
+
+    for (target, from) in targets {
+        if let Some((inst, _)) = insts.range(..target).next_back().filter(|(a, len)| target < *a + *len) {
+            findings.push((from, Finding::OverlappingTarget { inst: *inst }));
+        }
+    }
+    let mut diagnostics: Vec<Diagnostic> = findings.into_iter().map(|(addr, finding)| {
+        let self_written = written.range(addr..addr + insts.get(&addr).copied().unwrap_or(1)).next().is_some();
+        let severity = match finding {
+            Finding::OverlappingTarget { .. } => Severity::Warning,
+            _ if self_written => Severity::Warning,
+            _ => Severity::Error,
+        };
+        Diagnostic { addr, severity, finding }
+    }).collect();
+    diagnostics.sort_by_key(|d| d.addr);
+    diagnostics
+}
+
+#[test]
+fn test_lint() {
+    let prog = vec![
+        1101, 1, 2, 3,      // writes its own operand
+        1001, -1, 0, 30,    // negative address
+        1005, 30, 100,      // outside the image
+        1005, 30, 17,       // into the middle of the next instruction
+        1101, 0, 0, 99,
+        1301, 0, 0, 0,      // invalid mode
+        99,
+    ];
+    let diagnostics = lint(&prog);
+    let found: Vec<(usize, Severity, Finding)> = diagnostics.iter().map(|d| (d.addr, d.severity, d.finding)).collect();
+    assert_eq!(found, vec![
+        (4, Severity::Error, Finding::NegativeAddress { param: 0, addr: -1 }),
+        (8, Severity::Error, Finding::JumpOutsideImage { target: 100 }),
+        (11, Severity::Warning, Finding::OverlappingTarget { inst: 14 }),
+        (18, Severity::Error, Finding::InvalidMode { param: 0, mode: 3 }),
+    ]);
+    assert_eq!(diagnostics[1].to_string(), "8: error: jump to 100 outside the image");
+    assert_eq!(lint(&[11101, 1, 1, 0, 99]), vec![
+        Diagnostic { addr: 0, severity: Severity::Error, finding: Finding::ImmediateWrite { param: 2 } },
+    ]);
+
+    // the puzzle inputs only trip over self-modifying code
+    for input in [include_str!("../../input/2"), include_str!("../../input/5"),
+                  include_str!("../../input/7"), include_str!("../../input/9")] {
+        let mem = super::program::Program::parse(input).unwrap().words;
+        assert!(lint(&mem).iter().all(|d| d.severity == Severity::Warning), "{:?}", lint(&mem));
+    }
+}
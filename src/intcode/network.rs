@@ -0,0 +1,242 @@
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::io::{Output, PollingInput};
+
+// reads of an empty queue return -1
+pub const EMPTY: Word = -1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Packet {
+    pub src: Word,
+    pub dest: Word,
+    pub x: Word,
+    pub y: Word,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Continue,
+    // deliver a packet to its destination NIC
+    Send(Packet),
+    Stop,
+}
+
+// owner of the captured address, e.g. a NAT
+pub trait Supervisor {
+    fn receive(&mut self, packet: Packet) -> Action;
+    // every NIC is polling an empty queue and there are no packets in flight
+    fn idle(&mut self) -> Action;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetworkExit {
+    // the supervisor returned `Action::Stop`
+    Stopped,
+    // the network is idle and the supervisor did not send anything
+    Idle,
+    // every NIC halted or faulted
+    Halted,
+}
+
+#[derive(Default)]
+pub struct PacketAssembler {
+    pending: Vec<Word>,
+    packets: Vec<(Word, Word, Word)>,
+}
+
+impl Output for PacketAssembler {
+    fn write(&mut self, val: Word) {
+        self.pending.push(val);
+        if let [dest, x, y] = self.pending[..] {
+            self.packets.push((dest, x, y));
+            self.pending.clear();
+        }
+    }
+}
+
+pub type Nic = IntcodeComputer<PollingInput, PacketAssembler>;
+
+// NICs polling this many times in a row without receiving anything are considered idle
+const IDLE_THRESHOLD: usize = 2;
+// instructions a NIC may run in one turn before the next NIC gets its turn
+const TIME_SLICE: usize = 1000;
+
+// packet-switched network of NICs running the same program. NIC `i` boots with its address
+// `i` as the first input, then sends packets as (destination, X, Y) output triples
+pub struct Network {
+    nics: Vec<Nic>,
+    states: Vec<ExecState>,
+    captured: Word,
+    log: Option<Vec<Packet>>,
+}
+
+impl Network {
+    pub fn new(mem: &[Word], size: usize, captured: Word) -> Self {
+        let nics = (0..size)
+            .map(|addr| {
+                let mut input = PollingInput::new(EMPTY);
+                input.push(addr as Word);
+                IntcodeComputer::new(mem.to_vec(), input, PacketAssembler::default())
+            })
+            .collect();
+        Network { nics, states: vec![ExecState::Running; size], captured, log: None }
+    }
+
+    pub fn enable_logging(&mut self) {
+        self.log.get_or_insert_with(Vec::new);
+    }
+
+    // every packet sent so far, in order, including dropped ones
+    pub fn packet_log(&self) -> &[Packet] {
+        self.log.as_deref().unwrap_or_default()
+    }
+
+    pub fn nic(&self, addr: usize) -> &Nic {
+        &self.nics[addr]
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        if let Some(log) = self.log.as_mut() {
+            log.push(packet);
+        }
+        if let Some(nic) = usize::try_from(packet.dest).ok().and_then(|dest| self.nics.get_mut(dest)) {
+            nic.input_mut().extend(&[packet.x, packet.y]);
+        }
+    }
+
+    // run one NIC until it polls its input, halts or has run for a full time slice
+    fn run_slice(&mut self, addr: usize) -> Vec<Packet> {
+        if self.states[addr].is_finished() {
+            return Vec::new();
+        }
+        let nic = &mut self.nics[addr];
+        let idle_reads = nic.input_ref().idle_reads();
+        for _ in 0..TIME_SLICE {
+            self.states[addr] = nic.step();
+            if self.states[addr] != ExecState::Running || nic.input_ref().idle_reads() != idle_reads {
+                break;
+            }
+        }
+        nic.output_mut().packets.drain(..)
+            .map(|(dest, x, y)| Packet { src: addr as Word, dest, x, y })
+            .collect()
+    }
+
+    // halted and faulted NICs no longer poll, only the running ones count
+    fn is_idle(&self) -> bool {
+        self.nics.iter().zip(&self.states)
+            .filter(|(_, state)| !state.is_finished())
+            .all(|(nic, _)| nic.input_ref().is_idle(IDLE_THRESHOLD))
+    }
+
+    pub fn run<S: Supervisor>(&mut self, supervisor: &mut S) -> NetworkExit {
+        loop {
+            let mut sent = false;
+            for addr in 0..self.nics.len() {
+                for packet in self.run_slice(addr) {
+                    sent = true;
+                    if packet.dest != self.captured {
+                        self.deliver(packet);
+                        continue;
+                    }
+                    if let Some(log) = self.log.as_mut() {
+                        log.push(packet);
+                    }
+                    match supervisor.receive(packet) {
+                        Action::Continue => (),
+                        Action::Send(packet) => self.deliver(packet),
+                        Action::Stop => return NetworkExit::Stopped,
+                    }
+                }
+            }
+            if self.states.iter().all(|s| s.is_finished()) {
+                return NetworkExit::Halted;
+            }
+            if !sent && self.is_idle() {
+                match supervisor.idle() {
+                    Action::Continue => return NetworkExit::Idle,
+                    Action::Send(packet) => self.deliver(packet),
+                    Action::Stop => return NetworkExit::Stopped,
+                }
+            }
+        }
+    }
+}
+
+
+// keeps the last packet sent to it, and sends it to address 0 whenever the network is idle.
+// stops when it would send the same Y twice in a row
+#[derive(Default)]
+pub struct Nat {
+    first: Option<Packet>,
+    last: Option<Packet>,
+    sent: Vec<Word>,
+}
+
+impl Nat {
+    pub fn first_received(&self) -> Option<Packet> {
+        self.first
+    }
+
+    // Y values sent to address 0, in order
+    pub fn sent(&self) -> &[Word] {
+        &self.sent
+    }
+}
+
+impl Supervisor for Nat {
+    fn receive(&mut self, packet: Packet) -> Action {
+        self.first.get_or_insert(packet);
+        self.last = Some(packet);
+        Action::Continue
+    }
+
+    fn idle(&mut self) -> Action {
+        let Some(packet) = self.last else { return Action::Continue };
+        if self.sent.last() == Some(&packet.y) {
+            return Action::Stop;
+        }
+        self.sent.push(packet.y);
+        Action::Send(Packet { src: packet.dest, dest: 0, ..packet })
+    }
+}
+
+#[test]
+fn test_nat() {
+    // every NIC forwards each received packet to 255, adding its address to Y
+    let prog = vec![3, 50, 3, 51, 1008, 51, -1, 53, 1005, 53, 2, 3, 52, 104, 255, 4, 51, 1, 52, 50, 52, 4, 52, 1105, 1, 2];
+
+    struct Kickstart(Nat);
+    impl Supervisor for Kickstart {
+        fn receive(&mut self, packet: Packet) -> Action {
+            self.0.receive(packet)
+        }
+        fn idle(&mut self) -> Action {
+            match self.0.last {
+                None => Action::Send(Packet { src: 255, dest: 3, x: 7, y: 10 }),
+                Some(_) => self.0.idle(),
+            }
+        }
+    }
+
+    let mut network = Network::new(&prog, 5, 255);
+    network.enable_logging();
+    let mut supervisor = Kickstart(Nat::default());
+    assert_eq!(network.run(&mut supervisor), NetworkExit::Stopped);
+    assert_eq!(supervisor.0.first_received(), Some(Packet { src: 3, dest: 255, x: 7, y: 13 }));
+    assert_eq!(supervisor.0.sent(), &[13]);
+    assert_eq!(network.packet_log().len(), 4);
+
+    // NIC 0 halts right away, the others poll forever
+    struct Quiet;
+    impl Supervisor for Quiet {
+        fn receive(&mut self, _packet: Packet) -> Action {
+            Action::Continue
+        }
+        fn idle(&mut self) -> Action {
+            Action::Continue
+        }
+    }
+    let prog = vec![3, 30, 1005, 30, 6, 99, 3, 31, 1105, 1, 6];
+    assert_eq!(Network::new(&prog, 3, 255).run(&mut Quiet), NetworkExit::Idle);
+}
@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+use super::inst::{Instruction, Word};
+use super::smc::CodeWrite;
+
+// execution events of an `IntcodeComputer`, see `IntcodeComputer::with_observer`.
+// every callback defaults to doing nothing, so an unused event costs nothing
+pub trait Observer {
+    // an instruction at `pc` is about to execute. an input instruction is reported
+    // once the input is available
+    fn on_fetch(&mut self, _pc: usize, _inst: &Instruction) {}
+    // operand read, including reads from devices
+    fn on_read(&mut self, _addr: usize, _val: Word) {}
+    fn on_write(&mut self, _addr: usize, _val: Word) {}
+    // taken jumps only
+    fn on_jump(&mut self, _from: usize, _to: usize) {}
+    fn on_relative_base(&mut self, _old: usize, _new: usize) {}
+    fn on_input(&mut self, _val: Word) {}
+    fn on_output(&mut self, _val: Word) {}
+    fn on_halt(&mut self, _pc: usize) {}
+    // a write into executed code, under `CodePolicy::Log`
+    fn on_code_write(&mut self, _write: &CodeWrite) {}
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+
+// how often each memory cell is read and written as an operand
+#[derive(Default, Debug)]
+pub struct AccessCounter {
+    pub reads: BTreeMap<usize, usize>,
+    pub writes: BTreeMap<usize, usize>,
+    pub instructions: usize,
+}
+
+impl Observer for AccessCounter {
+    fn on_fetch(&mut self, _pc: usize, _inst: &Instruction) {
+        self.instructions += 1;
+    }
+
+    fn on_read(&mut self, addr: usize, _val: Word) {
+        *self.reads.entry(addr).or_default() += 1;
+    }
+
+    fn on_write(&mut self, addr: usize, _val: Word) {
+        *self.writes.entry(addr).or_default() += 1;
+    }
+}
+
+#[test]
+fn test_access_counter() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+
+    let prog = vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50];
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default())
+        .with_observer(AccessCounter::default());
+    computer.run_until_finish();
+    let counter = computer.observer_ref();
+    assert_eq!(counter.instructions, 3);
+    assert_eq!(counter.reads.get(&3), Some(&1));
+    assert_eq!(counter.writes.keys().copied().collect::<Vec<_>>(), vec![0, 3]);
+}
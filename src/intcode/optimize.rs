@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Range;
+
+use super::computer::ExecState;
+use super::engine;
+use super::inst::{Cond, Instruction, Operation, Parameter, Word};
+use super::isa::IsaProfile;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Options {
+    // assume relative-mode operands never touch the program image, as with a stack placed
+    // after it. without this, any relative operand pins the whole program
+    pub stack_outside_image: bool,
+    // only emit opcodes and modes allowed by the profile
+    pub profile: Option<IsaProfile>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Stats {
+    pub folded: usize,
+    // position operands replaced by known constants
+    pub propagated: usize,
+    pub threaded: usize,
+    pub dead_stores: usize,
+    // instructions no longer executed, including dead stores
+    pub removed: usize,
+}
+
+pub struct Optimized {
+    pub mem: Vec<Word>,
+    pub stats: Stats,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Removal {
+    DeadStore,
+    // never taken, or to the next instruction
+    Jump,
+}
+
+struct Slot {
+    addr: usize,
+    len: usize,
+    inst: Instruction,
+    // read or written as data, or overlapping another instruction: kept as is
+    pinned: bool,
+    removed: Option<Removal>,
+}
+
+impl Slot {
+    fn ends_block(&self) -> bool {
+        self.inst.op == Operation::Halt || self.inst.jump_cond().is_some()
+    }
+}
+
+struct Optimizer {
+    options: Options,
+    image_len: usize,
+    slots: Vec<Slot>,
+    index: BTreeMap<usize, usize>,
+    blocks: Vec<Range<usize>>,
+    stats: Stats,
+}
+
+impl Optimizer {
+    fn new(mem: &[Word], options: Options) -> Self {
+        let mut insts: BTreeMap<usize, Instruction> = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        let mut computed_jumps = false;
+        let mut pending = vec![0];
+        while let Some(pc) = pending.pop() {
+            if insts.contains_key(&pc) {
+                continue;
+            }
+            let Some(inst) = Instruction::decode(mem, pc) else { continue };
+            let next = pc + inst.op.instruction_len();
+            match (inst.op, inst.jump_cond()) {
+                (Operation::Halt, _) => (),
+                (_, Some(cond)) => {
+                    match inst.params[1] {
+                        Parameter::Immediate(t) if t >= 0 => {
+                            pending.push(t as usize);
+                            leaders.insert(t as usize);
+                        },
+                        _ => computed_jumps = true,
+                    }
+                    if cond != Cond::Always {
+                        pending.push(next);
+                    }
+                    leaders.insert(next);
+                },
+                _ => pending.push(next),
+            }
+            insts.insert(pc, inst);
+        }
+        // a computed jump may land on any instruction whose address appears as a constant.
+        // so may a jump that is overwritten at runtime
+        let writes_code = insts.values().any(|i| match i.op.roles().1.map(|w| i.params[w]) {
+            Some(Parameter::AbsPosition(x)) => insts.range(..=x).next_back().is_some_and(|(a, i)| x < a + i.op.instruction_len()),
+            _ => false,
+        });
+        if computed_jumps || writes_code {
+            let constants: Vec<usize> = insts.values()
+                .flat_map(|i| i.params.iter())
+                .filter_map(|p| match p {
+                    Parameter::Immediate(v) if *v >= 0 => Some(*v as usize),
+                    _ => None,
+                })
+                .filter(|v| insts.contains_key(v))
+                .collect();
+            leaders.extend(constants);
+        }
+
+        let relative = insts.values().flat_map(|i| i.params.iter()).any(|p| matches!(p, Parameter::RelPosition(_)));
+        let mut slots: Vec<Slot> = insts.into_iter()
+            .map(|(addr, inst)| Slot { addr, len: inst.op.instruction_len(), inst, pinned: false, removed: None })
+            .collect();
+        let index: BTreeMap<usize, usize> = slots.iter().enumerate().map(|(i, s)| (s.addr, i)).collect();
+        let containing = |index: &BTreeMap<usize, usize>, slots: &[Slot], addr: usize| {
+            index.range(..=addr).next_back().map(|(_, i)| *i).filter(|i| addr < slots[*i].addr + slots[*i].len)
+        };
+        let mut pinned = BTreeSet::new();
+        for (i, slot) in slots.iter().enumerate() {
+            if relative && !options.stack_outside_image {
+                pinned.insert(i);
+            }
+            if i > 0 && slots[i - 1].addr + slots[i - 1].len > slot.addr {
+                pinned.extend([i - 1, i]);
+            }
+            for param in slot.inst.params.iter() {
+                if let Parameter::AbsPosition(x) = param {
+                    pinned.extend(containing(&index, &slots, *x));
+                }
+            }
+        }
+        for i in pinned {
+            slots[i].pinned = true;
+        }
+
+        let mut blocks: Vec<Range<usize>> = Vec::new();
+        for (i, slot) in slots.iter().enumerate() {
+            let split = match i.checked_sub(1).map(|p| &slots[p]) {
+                None => true,
+                Some(prev) => prev.ends_block() || prev.pinned || slot.pinned
+                    || prev.addr + prev.len != slot.addr || leaders.contains(&slot.addr),
+            };
+            match blocks.last_mut() {
+                Some(block) if !split => block.end = i + 1,
+                _ => blocks.push(i..i + 1),
+            }
+        }
+        Optimizer { options, image_len: mem.len(), slots, index, blocks, stats: Stats::default() }
+    }
+
+    fn allows(&self, opcode: Word, mode: i8) -> bool {
+        self.options.profile.is_none_or(|p| p.allows_opcode(opcode) && p.allows_mode(mode))
+    }
+
+    // whether a relative-mode access may touch `addr`
+    fn may_alias(&self, addr: usize) -> bool {
+        !self.options.stack_outside_image || addr >= self.image_len
+    }
+
+    // replace reads of cells with known constants and compute arithmetic on constants
+    fn propagate(&mut self, block: Range<usize>) {
+        let mut known: BTreeMap<usize, Word> = BTreeMap::new();
+        for i in block {
+            if self.slots[i].pinned {
+                continue;
+            }
+            let (reads, write) = self.slots[i].inst.op.roles();
+            for &r in reads {
+                if let Parameter::AbsPosition(x) = self.slots[i].inst.params[r] {
+                    if let Some(v) = known.get(&x).filter(|_| self.allows(self.slots[i].inst.op.opcode(), 1)) {
+                        self.slots[i].inst.params[r] = Parameter::Immediate(*v);
+                        self.stats.propagated += 1;
+                    }
+                }
+            }
+            let inst = &mut self.slots[i].inst;
+            let value = match (inst.op, &inst.params[..]) {
+                (Operation::Add, [Parameter::Immediate(a), Parameter::Immediate(b), _]) => a.checked_add(*b),
+                (Operation::Multiply, [Parameter::Immediate(a), Parameter::Immediate(b), _]) => a.checked_mul(*b),
+                (Operation::LessThan, [Parameter::Immediate(a), Parameter::Immediate(b), _]) => Some((a < b) as Word),
+                (Operation::Equals, [Parameter::Immediate(a), Parameter::Immediate(b), _]) => Some((a == b) as Word),
+                _ => None,
+            };
+            if let Some(v) = value {
+                let folded = [Parameter::Immediate(v), Parameter::Immediate(0), inst.params[2]];
+                if inst.op != Operation::Add || inst.params[..2] != folded[..2] {
+                    *inst = Instruction { op: Operation::Add, params: folded.to_vec() };
+                    self.stats.folded += 1;
+                }
+            }
+            match write.map(|w| inst.params[w]) {
+                Some(Parameter::AbsPosition(x)) => match value {
+                    Some(v) => { known.insert(x, v); },
+                    None => { known.remove(&x); },
+                },
+                Some(Parameter::RelPosition(_)) => {
+                    let options = self.options;
+                    let image_len = self.image_len;
+                    known.retain(|x, _| options.stack_outside_image && *x < image_len);
+                },
+                _ => (),
+            }
+        }
+    }
+
+    // retarget jumps to unconditional jumps, and drop jumps that change nothing
+    fn thread(&mut self) {
+        for i in 0..self.slots.len() {
+            let slot = &self.slots[i];
+            let Some(cond) = slot.inst.jump_cond().filter(|_| !slot.pinned) else { continue };
+            let Parameter::Immediate(mut target) = slot.inst.params[1] else { continue };
+            let mut visited = BTreeSet::new();
+            while let Some(next) = self.index.get(&(target as usize)).map(|j| &self.slots[*j]) {
+                let next_target = match (next.pinned, next.inst.jump_cond(), next.inst.params.get(1)) {
+                    (false, Some(Cond::Always), Some(Parameter::Immediate(t))) if *t >= 0 => *t,
+                    _ => break,
+                };
+                if !visited.insert(target) {
+                    break;
+                }
+                target = next_target;
+            }
+            let slot = &mut self.slots[i];
+            if slot.inst.params[1] != Parameter::Immediate(target) {
+                slot.inst.params[1] = Parameter::Immediate(target);
+                self.stats.threaded += 1;
+            }
+            if cond == Cond::Never || target as usize == slot.addr + slot.len {
+                slot.removed = Some(Removal::Jump);
+            }
+        }
+    }
+
+    // writes overwritten later in the block before anything reads them
+    fn eliminate_dead_stores(&mut self, block: Range<usize>) {
+        let mut overwritten: BTreeSet<usize> = BTreeSet::new();
+        for i in block.rev() {
+            let slot = &self.slots[i];
+            if slot.pinned || slot.removed.is_some() {
+                continue;
+            }
+            let (reads, write) = slot.inst.op.roles();
+            if let Some(Parameter::AbsPosition(x)) = write.map(|w| slot.inst.params[w]) {
+                if slot.inst.op != Operation::Input && overwritten.contains(&x) {
+                    self.slots[i].removed = Some(Removal::DeadStore);
+                    continue;
+                }
+                overwritten.insert(x);
+            }
+            for &r in reads {
+                match self.slots[i].inst.params[r] {
+                    Parameter::AbsPosition(y) => { overwritten.remove(&y); },
+                    Parameter::RelPosition(_) => overwritten.retain(|x| !self.may_alias(*x)),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // move the remaining instructions of a block to its start, jumping to its end if it
+    // falls through. all or nothing, since removals in a block depend on each other
+    fn pack(&mut self, block: Range<usize>, mem: &mut [Word]) {
+        let removed = self.slots[block.clone()].iter().filter(|s| s.removed.is_some()).count();
+        let slots = &self.slots[block.clone()];
+        let start = slots[0].addr;
+        let end = slots.last().map(|s| s.addr + s.len).unwrap();
+        let mut words: Vec<Word> = slots.iter().filter(|s| s.removed.is_none()).flat_map(|s| s.inst.encode()).collect();
+        let exits = slots.iter().rev().find(|s| s.removed.is_none())
+            .is_some_and(|s| s.inst.op == Operation::Halt || s.inst.jump_cond() == Some(Cond::Always));
+        let gap = end - start - words.len();
+        if removed > 0 && !exits && gap > 0 {
+            if gap < 3 || removed < 2 || !self.allows(5, 1) {
+                // keep the block as it is, apart from rewritten operands
+                self.slots[block.clone()].iter_mut().for_each(|s| s.removed = None);
+                words = self.slots[block].iter().flat_map(|s| s.inst.encode()).collect();
+            } else {
+                words.extend([1105, 1, end as Word]);
+            }
+        }
+        mem[start..start + words.len()].copy_from_slice(&words);
+    }
+
+    fn run(mut self, mem: &[Word]) -> Optimized {
+        let mut out = mem.to_vec();
+        for block in self.blocks.clone() {
+            self.propagate(block);
+        }
+        self.thread();
+        for block in self.blocks.clone() {
+            self.eliminate_dead_stores(block);
+        }
+        for block in self.blocks.clone() {
+            if !self.slots[block.start].pinned {
+                self.pack(block, &mut out);
+            }
+        }
+        self.stats.removed = self.slots.iter().filter(|s| s.removed.is_some()).count();
+        self.stats.dead_stores = self.slots.iter().filter(|s| s.removed == Some(Removal::DeadStore)).count();
+        Optimized { mem: out, stats: self.stats }
+    }
+}
+
+// rewrite the reachable code of `mem` without moving any jump target or any instruction
+// that is accessed as data. memory-mapped devices are not supported
+pub fn optimize(mem: &[Word], options: Options) -> Optimized {
+    Optimizer::new(mem, options).run(mem)
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Mismatch {
+    State { set: usize, original: ExecState, optimized: ExecState },
+    Outputs { set: usize, original: Vec<Word>, optimized: Vec<Word> },
+    Memory { set: usize, addr: usize, original: Word, optimized: Word },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::State { set, original, optimized } =>
+                write!(f, "input set {}: original ends {:?}, optimized ends {:?}", set, original, optimized),
+            Mismatch::Outputs { set, original, optimized } =>
+                write!(f, "input set {}: original outputs {:?}, optimized outputs {:?}", set, original, optimized),
+            Mismatch::Memory { set, addr, original, optimized } =>
+                write!(f, "input set {}: mem[{}] is {} originally, {} optimized", set, addr, original, optimized),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+// run both programs on every input set, for at most `max_steps` steps each, and compare
+// their final states, outputs and the memory cells the optimizer did not rewrite
+pub fn verify(original: &[Word], optimized: &[Word], input_sets: &[Vec<Word>], max_steps: usize) -> Result<(), Mismatch> {
+    for (set, inputs) in input_sets.iter().enumerate() {
+        let mut machines = [engine::computer(original.to_vec(), inputs), engine::computer(optimized.to_vec(), inputs)];
+        let mut states = [ExecState::Running; 2];
+        let mut written: BTreeSet<usize> = BTreeSet::new();
+        for (machine, state) in machines.iter_mut().zip(states.iter_mut()) {
+            for _ in 0..max_steps {
+                *state = machine.step();
+                if *state != ExecState::Running {
+                    break;
+                }
+            }
+            written.extend(machine.take_writes().into_iter().map(|(addr, _)| addr));
+        }
+        if states[0] != states[1] {
+            return Err(Mismatch::State { set, original: states[0], optimized: states[1] });
+        }
+        let outputs = machines.each_ref().map(|m| m.output_ref().to_vec());
+        if outputs[0] != outputs[1] {
+            let [original, optimized] = outputs;
+            return Err(Mismatch::Outputs { set, original, optimized });
+        }
+        let unchanged = (0..original.len().max(optimized.len())).filter(|a| original.get(*a) == optimized.get(*a));
+        for addr in unchanged.chain(written.into_iter().filter(|a| *a >= original.len())) {
+            let [a, b] = machines.each_ref().map(|m| m.peek(addr));
+            if a != b {
+                return Err(Mismatch::Memory { set, addr, original: a, optimized: b });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_optimize() {
+    // 30 = 2 + 3; 31 = 30 * 4; 32 = 0 (dead); 32 = 1; print 31, 32
+    let prog = vec![1101, 2, 3, 30, 1002, 30, 4, 31, 1101, 0, 0, 32, 1101, 0, 1, 32, 4, 31, 4, 32, 99];
+    let optimized = optimize(&prog, Options::default());
+    assert_eq!(optimized.stats, Stats { folded: 3, propagated: 3, threaded: 0, dead_stores: 1, removed: 1 });
+    assert_eq!(verify(&prog, &optimized.mem, &[vec![]], 1000), Ok(()));
+    assert_ne!(prog, optimized.mem);
+
+    // jumps through the jump at 9 when the input is non-zero
+    let prog = vec![3, 20, 1005, 20, 9, 104, 0, 99, 99, 1105, 1, 12, 104, 1, 99];
+    let optimized = optimize(&prog, Options::default());
+    assert_eq!(optimized.stats, Stats { threaded: 1, ..Default::default() });
+    assert_eq!(optimized.mem[4], 12);
+    assert_eq!(verify(&prog, &optimized.mem, &[vec![0], vec![1]], 1000), Ok(()));
+
+    // the diagnostic writes its own instructions before running them, so it is left alone
+    let diagnostic = super::program::Program::parse(include_str!("../../input/5")).unwrap().words;
+    assert_eq!(optimize(&diagnostic, Options::default()).mem, diagnostic);
+
+    let boost = super::program::Program::parse(include_str!("../../input/9")).unwrap().words;
+    assert_eq!(optimize(&boost, Options::default()).mem, boost);
+    let optimized = optimize(&boost, Options { stack_outside_image: true, profile: None });
+    assert!(optimized.stats.folded > 0);
+    assert_eq!(verify(&boost, &optimized.mem, &[vec![1], vec![2]], 1_000_000), Ok(()));
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use super::image::{self, ImageError};
+use super::inst::Word;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Program {
+    pub words: Vec<Word>,
+    pub name: Option<String>,
+    pub path: Option<PathBuf>,
+    // address -> name, e.g. debug symbols stored in a program image
+    pub symbols: BTreeMap<usize, String>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    // `index` is the position of the offending comma separated field
+    Parse { index: usize, text: String },
+    Image(ImageError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "cannot read program: {}", e),
+            LoadError::Parse { index, text } => write!(f, "invalid field #{}: {:?}", index, text),
+            LoadError::Image(e) => write!(f, "invalid program image: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            LoadError::Image(e) => Some(e),
+            LoadError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl Program {
+    pub fn new(words: Vec<Word>) -> Self {
+        Program { words, name: None, path: None, symbols: BTreeMap::new() }
+    }
+
+    // comma separated words. whitespace and newlines around fields are ignored,
+    // and so is a trailing comma
+    pub fn parse(text: &str) -> Result<Self, LoadError> {
+        let fields: Vec<&str> = text.split(',').map(str::trim).collect();
+        let count = if fields.last() == Some(&"") { fields.len() - 1 } else { fields.len() };
+        let words = fields[..count].iter().enumerate()
+            .map(|(index, field)| field.parse().map_err(|_| LoadError::Parse { index, text: field.to_string() }))
+            .collect::<Result<_, _>>()?;
+        Ok(Program::new(words))
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, LoadError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Self::parse(&text)
+    }
+
+    // either text or a binary image, see `intcode::image`. named after the file name
+    // unless the image has a name
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let mut program = if image::is_image(&data) {
+            image::decode(&data).map_err(LoadError::Image)?
+        } else {
+            let text = String::from_utf8(data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Self::parse(&text)?
+        };
+        if program.name.is_none() {
+            program.name = path.file_name().map(|name| name.to_string_lossy().into_owned());
+        }
+        program.path = Some(path.to_path_buf());
+        Ok(program)
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(Program::parse(" 1, 2,\n3 ,99,\n").unwrap().words, vec![1, 2, 3, 99]);
+    assert_eq!(Program::parse("\n").unwrap().words, vec![]);
+    match Program::parse("1,2,,99") {
+        Err(LoadError::Parse { index, text }) => assert_eq!((index, text.as_str()), (2, "")),
+        r => panic!("unexpected {:?}", r),
+    }
+    match Program::parse("1,2x,99") {
+        Err(LoadError::Parse { index, text }) => assert_eq!((index, text.as_str()), (1, "2x")),
+        r => panic!("unexpected {:?}", r),
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::computer::IntcodeComputer;
+use super::inst::Word;
+use super::observer::Observer;
+
+// compares each candidate cell with its value at the previous scan
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(Word),
+}
+
+impl Filter {
+    fn matches(&self, old: Word, new: Word) -> bool {
+        match self {
+            Filter::Changed => new != old,
+            Filter::Unchanged => new == old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::Equals(val) => new == *val,
+        }
+    }
+}
+
+// narrows down the memory cells of a paused machine that behave like a game variable.
+// scan between pauses, for example whenever the program waits for input
+pub struct Scanner {
+    candidates: BTreeMap<usize, Word>,
+    // the cells the machine had at the last scan
+    known: BTreeSet<usize>,
+    // whether a cell that was 0 at every scan still matches all filters so far
+    zeros: bool,
+}
+
+impl Scanner {
+    // every cell of the machine is a candidate
+    pub fn new<IN, OUT, OBS: Observer>(computer: &IntcodeComputer<IN, OUT, OBS>) -> Self {
+        let candidates: BTreeMap<usize, Word> = computer.cells().collect();
+        Scanner { known: candidates.keys().copied().collect(), candidates, zeros: true }
+    }
+
+    // keep the candidates matching `filter`, returning how many remain. cells first written
+    // since the last scan were 0 until then, and are admitted if 0 matched the earlier filters
+    pub fn scan<IN, OUT, OBS: Observer>(&mut self, computer: &IntcodeComputer<IN, OUT, OBS>, filter: Filter) -> usize {
+        for (addr, _) in computer.cells() {
+            if self.known.insert(addr) && self.zeros {
+                self.candidates.insert(addr, 0);
+            }
+        }
+        self.zeros &= filter.matches(0, 0);
+        self.candidates.retain(|addr, old| {
+            let new = computer.peek(*addr);
+            let keep = filter.matches(*old, new);
+            *old = new;
+            keep
+        });
+        self.candidates.len()
+    }
+
+    pub fn candidates(&self) -> Vec<usize> {
+        self.candidates.keys().copied().collect()
+    }
+
+    // the only candidate left, if the scans have narrowed it down
+    pub fn found(&self) -> Option<usize> {
+        match self.candidates.len() {
+            1 => self.candidates.keys().next().copied(),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_scanner() {
+    use super::computer::ExecState;
+    use super::io::{BufferInput, BufferOutput};
+
+    // each round reads a move, adds 10 to the score at 101 and loses a life at 102 on a
+    // zero move. 103 is never touched
+    let mut prog = vec![3, 100, 1001, 101, 10, 101, 1005, 100, 13, 1001, 102, -1, 102, 4, 101, 1105, 1, 0];
+    prog.resize(104, 0);
+    prog[102] = 3;
+    prog[103] = 7;
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default());
+    let play = |computer: &mut IntcodeComputer<BufferInput, BufferOutput>, input| {
+        computer.input_mut().push(input);
+        assert_eq!(computer.run_until_blocked(), ExecState::WaitingInput);
+    };
+    assert_eq!(computer.run_until_blocked(), ExecState::WaitingInput);
+
+    let mut score = Scanner::new(&computer);
+    play(&mut computer, 1);
+    score.scan(&computer, Filter::Increased);
+    play(&mut computer, 1);
+    assert_eq!(score.scan(&computer, Filter::Increased), 1);
+    assert_eq!(score.found(), Some(101));
+
+    let mut lives = Scanner::new(&computer);
+    play(&mut computer, 0);
+    assert_eq!(lives.scan(&computer, Filter::Equals(2)), 1);
+    assert_eq!(lives.found(), Some(102));
+    let mut unchanged = Scanner::new(&computer);
+    play(&mut computer, 1);
+    unchanged.scan(&computer, Filter::Unchanged);
+    assert!(unchanged.candidates().contains(&103));
+
+    computer.freeze(102, 9);
+    play(&mut computer, 0);
+    assert_eq!(computer.peek(102), 9);
+    computer.poke(101, 1000);
+    play(&mut computer, 1);
+    assert_eq!(computer.output_ref().last(), Some(&1010));
+}
+
+#[test]
+fn test_scanner_new_cells() {
+    use super::computer::ExecState;
+    use super::io::{BufferInput, BufferOutput};
+
+    // stores each move at 50 and counts the rounds at 200, both past the end of the program
+    let prog = vec![3, 50, 1001, 200, 1, 200, 1105, 1, 0];
+    let play = |computer: &mut IntcodeComputer<BufferInput, BufferOutput>, input| {
+        computer.input_mut().push(input);
+        assert_eq!(computer.run_until_blocked(), ExecState::WaitingInput);
+    };
+    let mut computer = IntcodeComputer::new(prog.clone(), BufferInput::new(&[]), BufferOutput::default());
+    assert_eq!(computer.run_until_blocked(), ExecState::WaitingInput);
+    let mut rounds = Scanner::new(&computer);
+    play(&mut computer, 1);
+    assert_eq!(rounds.scan(&computer, Filter::Increased), 2);
+    play(&mut computer, 1);
+    assert_eq!(rounds.scan(&computer, Filter::Increased), 1);
+    assert_eq!(rounds.found(), Some(200));
+
+    // a cell that stayed 0 through a scan for increases is not admitted later
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default());
+    assert_eq!(computer.run_until_blocked(), ExecState::WaitingInput);
+    let mut scanner = Scanner::new(&computer);
+    assert_eq!(scanner.scan(&computer, Filter::Increased), 0);
+    play(&mut computer, 1);
+    assert_eq!(scanner.scan(&computer, Filter::Changed), 0);
+}
@@ -0,0 +1,241 @@
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::io::{Input, Output, Pipe};
+use super::isa::Fault;
+
+pub type MachineId = usize;
+
+struct Slot {
+    computer: IntcodeComputer<Pipe, Pipe>,
+    inbox: Pipe,
+    outbox: Pipe,
+    state: ExecState,
+    targets: Vec<MachineId>,
+    collected: Option<Vec<Word>>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RunResult {
+    // every machine halted or faulted
+    Finished,
+    // no machine can make progress but some are still waiting for input
+    Quiescent(QuiescenceReport),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Waiter {
+    pub machine: MachineId,
+    // machines connected to the input queue of `machine`
+    pub sources: Vec<MachineId>,
+}
+
+// snapshot of a scheduler where every machine is either halted or blocked on an empty
+// input queue, and there are no messages left to deliver to a running machine
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QuiescenceReport {
+    pub waiting: Vec<Waiter>,
+    pub halted: Vec<MachineId>,
+    pub faulted: Vec<(MachineId, Fault)>,
+    // values queued for halted or faulted machines, which will never be read
+    pub undelivered: Vec<(MachineId, usize)>,
+}
+
+// handed to the supervisor of `Scheduler::run_supervised` to restart a quiescent network
+pub struct Injector<'a> {
+    scheduler: &'a mut Scheduler,
+    injected: bool,
+}
+
+impl Injector<'_> {
+    pub fn push_input(&mut self, id: MachineId, val: Word) {
+        self.scheduler.push_input(id, val);
+        self.injected = true;
+    }
+
+    pub fn scheduler(&self) -> &Scheduler {
+        self.scheduler
+    }
+}
+
+// round-robin scheduler for machines on a single thread. the current machine runs until it
+// waits for input, halts or writes an output; outputs are then routed to the connected
+// machines and the next machine gets its turn, so the interleaving is always the same
+#[derive(Default)]
+pub struct Scheduler {
+    slots: Vec<Slot>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, mem: Vec<Word>) -> MachineId {
+        let inbox = Pipe::default();
+        let outbox = Pipe::default();
+        self.slots.push(Slot {
+            computer: IntcodeComputer::new(mem, inbox.clone(), outbox.clone()),
+            inbox,
+            outbox,
+            state: ExecState::Running,
+            targets: Vec::new(),
+            collected: None,
+        });
+        self.slots.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // every output of `from` is copied to the input of `to`
+    pub fn connect(&mut self, from: MachineId, to: MachineId) {
+        assert!(to < self.slots.len(), "unknown machine {}", to);
+        self.slots[from].targets.push(to);
+    }
+
+    pub fn push_input(&mut self, id: MachineId, val: Word) {
+        self.slots[id].inbox.write(val);
+    }
+
+    // record the outputs of `id`, in addition to routing them
+    pub fn collect(&mut self, id: MachineId) {
+        self.slots[id].collected.get_or_insert_with(Vec::new);
+    }
+
+    pub fn collected(&self, id: MachineId) -> &[Word] {
+        self.slots[id].collected.as_deref().unwrap_or_default()
+    }
+
+    pub fn state(&self, id: MachineId) -> ExecState {
+        self.slots[id].state
+    }
+
+    pub fn computer(&self, id: MachineId) -> &IntcodeComputer<Pipe, Pipe> {
+        &self.slots[id].computer
+    }
+
+    fn route_outputs(&mut self, id: MachineId) {
+        while let Some(val) = self.slots[id].outbox.read() {
+            for i in 0..self.slots[id].targets.len() {
+                let target = self.slots[id].targets[i];
+                self.slots[target].inbox.write(val);
+            }
+            if let Some(collected) = self.slots[id].collected.as_mut() {
+                collected.push(val);
+            }
+        }
+    }
+
+    // give `id` one turn. returns whether it executed any instruction
+    fn run_slice(&mut self, id: MachineId) -> bool {
+        let mut progress = false;
+        let slot = &mut self.slots[id];
+        while !slot.state.is_finished() {
+            slot.state = slot.computer.step();
+            if slot.state != ExecState::Running {
+                break;
+            }
+            progress = true;
+            if !slot.outbox.is_empty() {
+                break;
+            }
+        }
+        self.route_outputs(id);
+        progress
+    }
+
+    // one turn for every machine, in order. returns whether any machine made progress
+    pub fn run_round(&mut self) -> bool {
+        let mut progress = false;
+        for id in 0..self.slots.len() {
+            progress |= self.run_slice(id);
+        }
+        progress
+    }
+
+    pub fn quiescence_report(&self) -> QuiescenceReport {
+        let sources = |id: MachineId| -> Vec<MachineId> {
+            (0..self.slots.len()).filter(|src| self.slots[*src].targets.contains(&id)).collect()
+        };
+        let mut report = QuiescenceReport {
+            waiting: Vec::new(), halted: Vec::new(), faulted: Vec::new(), undelivered: Vec::new(),
+        };
+        for (id, slot) in self.slots.iter().enumerate() {
+            match slot.state {
+                ExecState::Halted => report.halted.push(id),
+                ExecState::Faulted(fault) => report.faulted.push((id, fault)),
+                _ => report.waiting.push(Waiter { machine: id, sources: sources(id) }),
+            }
+            if slot.state.is_finished() && !slot.inbox.is_empty() {
+                report.undelivered.push((id, slot.inbox.len()));
+            }
+        }
+        report
+    }
+
+    pub fn run(&mut self) -> RunResult {
+        self.run_supervised(|_, _| {})
+    }
+
+    // like `run`, but on quiescence `supervisor` may inject inputs, e.g. as a NAT or a
+    // watchdog. the run resumes if it did, otherwise the report is returned
+    pub fn run_supervised<F>(&mut self, mut supervisor: F) -> RunResult
+    where F: FnMut(&QuiescenceReport, &mut Injector<'_>) {
+        loop {
+            let progress = self.run_round();
+            if self.slots.iter().all(|slot| slot.state.is_finished()) {
+                return RunResult::Finished;
+            }
+            if !progress {
+                let report = self.quiescence_report();
+                let mut injector = Injector { scheduler: self, injected: false };
+                supervisor(&report, &mut injector);
+                if !injector.injected {
+                    return RunResult::Quiescent(report);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_interleaving() {
+    // output the input plus one, three times
+    let prog = vec![3, 20, 1001, 20, 1, 20, 4, 20, 1001, 21, 1, 21, 1007, 21, 3, 22, 1005, 22, 0, 99];
+    let mut scheduler = Scheduler::new();
+    let a = scheduler.add(prog.clone());
+    let b = scheduler.add(prog);
+    scheduler.connect(a, b);
+    scheduler.connect(b, a);
+    scheduler.collect(b);
+    scheduler.push_input(a, 0);
+    assert_eq!(scheduler.run(), RunResult::Finished);
+    assert_eq!(scheduler.collected(b), &[2, 4, 6]);
+}
+
+#[test]
+fn test_quiescence() {
+    // output the sum of every two inputs, forever
+    let prog = vec![3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 1105, 1, 0];
+    let mut scheduler = Scheduler::new();
+    let a = scheduler.add(prog.clone());
+    let b = scheduler.add(prog);
+    scheduler.connect(a, b);
+    scheduler.collect(b);
+
+    let mut rounds = 0;
+    let result = scheduler.run_supervised(|report, injector| {
+        assert_eq!(report.waiting, vec![Waiter { machine: a, sources: vec![] }, Waiter { machine: b, sources: vec![a] }]);
+        if rounds < 4 {
+            injector.push_input(a, rounds);
+            rounds += 1;
+        }
+    });
+    assert!(matches!(result, RunResult::Quiescent(ref report) if report.halted.is_empty()));
+    assert_eq!(scheduler.collected(b), &[6]);
+}
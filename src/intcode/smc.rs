@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::inst::Word;
+
+// what happens when a program writes to an address it has executed as code
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CodePolicy {
+    // record the write in the report
+    Allow,
+    // also report it to the observer as it happens, see `Observer::on_code_write`
+    Log,
+    // fault before the write happens, with the pc at the writing instruction
+    Trap,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CodeWrite {
+    pub pc: usize,
+    pub addr: usize,
+    pub old: Word,
+    pub new: Word,
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instruction at {} overwrites code at {}: {} -> {}", self.pc, self.addr, self.old, self.new)
+    }
+}
+
+// addresses executed as code so far, and the writes into them
+#[derive(Clone, Debug)]
+pub struct CodeGuard {
+    pub policy: CodePolicy,
+    executed: BTreeSet<usize>,
+    writes: Vec<CodeWrite>,
+}
+
+impl CodeGuard {
+    pub fn new(policy: CodePolicy) -> Self {
+        CodeGuard { policy, executed: BTreeSet::new(), writes: Vec::new() }
+    }
+
+    pub fn is_code(&self, addr: usize) -> bool {
+        self.executed.contains(&addr)
+    }
+
+    pub fn executed(&self) -> &BTreeSet<usize> {
+        &self.executed
+    }
+
+    pub fn writes(&self) -> &[CodeWrite] {
+        &self.writes
+    }
+
+    // the distinct instructions that wrote into code, with every address each of them modified
+    pub fn sites(&self) -> Vec<(usize, BTreeSet<usize>)> {
+        let mut sites: Vec<(usize, BTreeSet<usize>)> = Vec::new();
+        for write in self.writes.iter() {
+            match sites.iter_mut().find(|(pc, _)| *pc == write.pc) {
+                Some((_, addrs)) => { addrs.insert(write.addr); },
+                None => sites.push((write.pc, BTreeSet::from([write.addr]))),
+            }
+        }
+        sites.sort();
+        sites
+    }
+
+    pub(super) fn mark_executed(&mut self, pc: usize, len: usize) {
+        self.executed.extend(pc..pc + len);
+    }
+
+    pub(super) fn record(&mut self, write: CodeWrite) {
+        self.writes.push(write);
+    }
+}
+
+#[test]
+fn test_code_guard() {
+    use super::computer::{ExecState, IntcodeComputer};
+    use super::io::{BufferInput, BufferOutput};
+    use super::isa::Fault;
+
+    // decrements 20, jumps around, then overwrites the first instruction
+    let prog = vec![1001, 20, -1, 20, 1005, 20, 13, 1101, 1, 0, 0, 99, 0, 1105, 1, 7,
+                    0, 0, 0, 0, 2];
+    let mut computer = IntcodeComputer::new(prog.clone(), BufferInput::new(&[]), BufferOutput::default());
+    computer.guard_code(CodePolicy::Allow);
+    computer.run_until_finish();
+    let guard = computer.code_guard().unwrap();
+    assert_eq!(guard.writes(), &[CodeWrite { pc: 7, addr: 0, old: 1001, new: 1 }]);
+    assert_eq!(guard.sites(), vec![(7, BTreeSet::from([0]))]);
+    assert!(guard.is_code(13) && !guard.is_code(20));
+
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default());
+    computer.guard_code(CodePolicy::Trap);
+    assert_eq!(computer.run_until_blocked(), ExecState::Faulted(Fault::CodeWrite { pc: 7, addr: 0 }));
+    assert_eq!(computer.pc(), 7);
+    assert_eq!(computer.peek(0), 1001);
+
+    // overwrites its own operand the first time it runs
+    let mut computer = IntcodeComputer::new(vec![1101, 5, 5, 1, 99], BufferInput::new(&[]), BufferOutput::default());
+    computer.guard_code(CodePolicy::Trap);
+    assert_eq!(computer.run_until_blocked(), ExecState::Faulted(Fault::CodeWrite { pc: 0, addr: 1 }));
+
+    // the day 5 diagnostic reuses its first instruction as scratch space
+    let mem = super::program::Program::parse(include_str!("../../input/5")).unwrap().words;
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(&[5]), BufferOutput::default());
+    computer.guard_code(CodePolicy::Allow);
+    computer.run_until_finish();
+    let sites = computer.code_guard().unwrap().sites();
+    assert_eq!(sites, vec![(284, BTreeSet::from([0])), (304, BTreeSet::from([0]))]);
+}
+
+
+#[test]
+fn test_code_guard_day9() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+    use super::observer::Observer;
+
+    #[derive(Default)]
+    struct Log(Vec<CodeWrite>);
+    impl Observer for Log {
+        fn on_code_write(&mut self, write: &CodeWrite) {
+            self.0.push(*write);
+        }
+    }
+
+    // the BOOST self-test dispatches on the input through a table of jumps, all of
+    // them executed, but it never writes into code
+    let mem = super::program::Program::parse(include_str!("../../input/9")).unwrap().words;
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(&[1]), BufferOutput::default())
+        .with_observer(Log::default());
+    computer.guard_code(CodePolicy::Log);
+    computer.run_until_finish();
+    let guard = computer.code_guard().unwrap();
+    assert!(guard.is_code(31) && guard.is_code(65));
+    assert!(guard.sites().is_empty() && computer.observer_ref().0.is_empty());
+
+    // under Log the observer sees each write as it happens
+    let prog = vec![1101, 7, 0, 0, 99];
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default())
+        .with_observer(Log::default());
+    computer.guard_code(CodePolicy::Log);
+    computer.run_until_finish();
+    assert_eq!(computer.observer_ref().0, vec![CodeWrite { pc: 0, addr: 0, old: 1101, new: 7 }]);
+    assert_eq!(computer.observer_ref().0, computer.code_guard().unwrap().writes());
+}
@@ -0,0 +1,248 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::io::{Input, Output};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Framing {
+    // every word is 8 bytes, little endian
+    Binary,
+    // every word is a decimal number on its own line, handy for netcat and scripts
+    Line,
+}
+
+pub struct StreamInput<R> {
+    reader: BufReader<R>,
+    framing: Framing,
+    error: Option<io::Error>,
+}
+
+impl<R: Read> StreamInput<R> {
+    pub fn new(reader: R, framing: Framing) -> Self {
+        StreamInput { reader: BufReader::new(reader), framing, error: None }
+    }
+
+    // the error that ended the input, if it did not end cleanly
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn read_word(&mut self) -> io::Result<Option<Word>> {
+        match self.framing {
+            Framing::Binary => {
+                let mut buf = [0u8; 8];
+                let mut len = 0;
+                while len < buf.len() {
+                    match self.reader.read(&mut buf[len..]) {
+                        Ok(0) => break,
+                        Ok(n) => len += n,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                        Err(e) => return Err(e),
+                    }
+                }
+                // the end of the stream only counts as the end of input between words
+                match len {
+                    0 => Ok(None),
+                    8 => Ok(Some(Word::from_le_bytes(buf))),
+                    _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("stream ended after {} bytes of a word", len))),
+                }
+            },
+            Framing::Line => {
+                let mut line = String::new();
+                loop {
+                    line.clear();
+                    if self.reader.read_line(&mut line)? == 0 {
+                        return Ok(None);
+                    }
+                    let line = line.trim();
+                    if !line.is_empty() {
+                        return line.parse().map(Some)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                    }
+                }
+            },
+        }
+    }
+}
+
+// a closed or broken stream reads as exhausted input, a broken one keeps its error
+impl<R: Read> Input for StreamInput<R> {
+    fn read(&mut self) -> Option<Word> {
+        match self.read_word() {
+            Ok(val) => val,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            },
+        }
+    }
+}
+
+pub struct StreamOutput<W> {
+    writer: W,
+    framing: Framing,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> StreamOutput<W> {
+    pub fn new(writer: W, framing: Framing) -> Self {
+        StreamOutput { writer, framing, error: None }
+    }
+
+    // the first error writing to the stream. later outputs are dropped
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn write_word(&mut self, val: Word) -> io::Result<()> {
+        match self.framing {
+            Framing::Binary => self.writer.write_all(&val.to_le_bytes())?,
+            Framing::Line => writeln!(self.writer, "{}", val)?,
+        }
+        self.writer.flush()
+    }
+}
+
+// the peer may have gone away, the machine keeps running like with a dropped channel
+impl<W: Write> Output for StreamOutput<W> {
+    fn write(&mut self, val: Word) {
+        if self.error.is_none() {
+            self.error = self.write_word(val).err();
+        }
+    }
+}
+
+
+pub type TcpComputer = IntcodeComputer<StreamInput<TcpStream>, StreamOutput<TcpStream>>;
+
+pub fn tcp_io(stream: TcpStream, framing: Framing) -> io::Result<(StreamInput<TcpStream>, StreamOutput<TcpStream>)> {
+    Ok((StreamInput::new(stream.try_clone()?, framing), StreamOutput::new(stream, framing)))
+}
+
+pub fn connect_tcp<A: ToSocketAddrs>(mem: Vec<Word>, addr: A, framing: Framing) -> io::Result<TcpComputer> {
+    let (input, output) = tcp_io(TcpStream::connect(addr)?, framing)?;
+    Ok(IntcodeComputer::new(mem, input, output))
+}
+
+// run until the machine halts. a client that stops sending early or stops reading is an error
+fn serve<R: Read, W: Write>(mem: Vec<Word>, input: StreamInput<R>, output: StreamOutput<W>) -> io::Result<()> {
+    let mut computer = IntcodeComputer::new(mem, input, output);
+    let state = computer.run_until_blocked();
+    if let Some(e) = computer.output_mut().take_error() {
+        return Err(e);
+    }
+    match state {
+        ExecState::Halted => Ok(()),
+        ExecState::Faulted(fault) => Err(io::Error::new(io::ErrorKind::InvalidData, fault)),
+        _ => Err(computer.input_mut().take_error()
+            .unwrap_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "input exhausted"))),
+    }
+}
+
+// serve one client: its writes are the machine's input, the machine's output is sent back
+pub fn serve_tcp(mem: Vec<Word>, listener: &TcpListener, framing: Framing) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    let (input, output) = tcp_io(stream, framing)?;
+    serve(mem, input, output)
+}
+
+#[cfg(unix)]
+pub type UnixComputer = IntcodeComputer<StreamInput<UnixStream>, StreamOutput<UnixStream>>;
+
+#[cfg(unix)]
+pub fn unix_io(stream: UnixStream, framing: Framing) -> io::Result<(StreamInput<UnixStream>, StreamOutput<UnixStream>)> {
+    Ok((StreamInput::new(stream.try_clone()?, framing), StreamOutput::new(stream, framing)))
+}
+
+#[cfg(unix)]
+pub fn connect_unix<P: AsRef<Path>>(mem: Vec<Word>, path: P, framing: Framing) -> io::Result<UnixComputer> {
+    let (input, output) = unix_io(UnixStream::connect(path)?, framing)?;
+    Ok(IntcodeComputer::new(mem, input, output))
+}
+
+#[cfg(unix)]
+pub fn serve_unix(mem: Vec<Word>, listener: &UnixListener, framing: Framing) -> io::Result<()> {
+    let (stream, _) = listener.accept()?;
+    let (input, output) = unix_io(stream, framing)?;
+    serve(mem, input, output)
+}
+
+#[test]
+fn test_serve_tcp() {
+    // outputs input * 2 until it reads 0
+    let prog = vec![3, 20, 1006, 20, 14, 1002, 20, 2, 21, 4, 21, 1105, 1, 0, 99];
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || serve_tcp(prog, &listener, Framing::Line));
+
+    let stream = TcpStream::connect(addr).unwrap();
+    let (mut input, mut output) = tcp_io(stream, Framing::Line).unwrap();
+    output.write(21);
+    assert_eq!(input.read(), Some(42));
+    output.write(-4);
+    assert_eq!(input.read(), Some(-8));
+    output.write(0);
+    assert_eq!(input.read(), None);
+    server.join().unwrap().unwrap();
+
+    // a client that sends garbage or leaves early fails the server instead of panicking it
+    for (request, kind) in [("x\n", io::ErrorKind::InvalidData), ("21\n", io::ErrorKind::UnexpectedEof)] {
+        let prog = vec![3, 20, 1006, 20, 14, 1002, 20, 2, 21, 4, 21, 1105, 1, 0, 99];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || serve_tcp(prog, &listener, Framing::Line));
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        stream.shutdown(std::net::Shutdown::Write).unwrap();
+        assert_eq!(server.join().unwrap().unwrap_err().kind(), kind);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serve_unix() {
+    let path = std::env::temp_dir().join(format!("adv2019-socket-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let prog = vec![3, 20, 1006, 20, 14, 1002, 20, 2, 21, 4, 21, 1105, 1, 0, 99];
+    let listener = UnixListener::bind(&path).unwrap();
+    let server = std::thread::spawn(move || serve_unix(prog, &listener, Framing::Binary));
+
+    let (mut input, mut output) = unix_io(UnixStream::connect(&path).unwrap(), Framing::Binary).unwrap();
+    output.write(21);
+    assert_eq!(input.read(), Some(42));
+    output.write(0);
+    assert_eq!(input.read(), None);
+    server.join().unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stream_errors() {
+    struct Closed;
+    impl Write for Closed {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // a word cut off after 3 bytes
+    let mut input = StreamInput::new(&[1u8, 0, 0][..], Framing::Binary);
+    assert_eq!(input.read(), None);
+    assert_eq!(input.take_error().unwrap().kind(), io::ErrorKind::InvalidData);
+    let mut input = StreamInput::new(&[][..], Framing::Binary);
+    assert_eq!(input.read(), None);
+    assert!(input.take_error().is_none());
+
+    // a client that stopped reading fails the server even though the machine halted
+    let err = serve(vec![104, 1, 99], StreamInput::new(&[][..], Framing::Line), StreamOutput::new(Closed, Framing::Line));
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,214 @@
+use std::any::Any;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use super::computer::{ExecState, IntcodeComputer};
+use super::inst::Word;
+use super::io::{BufferInput, BufferOutput, Input, Output};
+use super::isa::IsaProfile;
+use super::observer::Observer;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    // a candidate running longer than this is stopped with `Verdict::Timeout`
+    pub max_steps: usize,
+    pub profile: Option<IsaProfile>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { max_steps: 1_000_000, profile: None }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    // halted, faulted or out of input
+    Stopped(ExecState),
+    Timeout,
+    // the interpreter panicked, with the panic message
+    Panic(String),
+}
+
+impl Verdict {
+    pub fn is_crash(&self) -> bool {
+        matches!(self, Verdict::Panic(_) | Verdict::Stopped(ExecState::Faulted(_)))
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(s), _) => s.to_string(),
+        (_, Some(s)) => s.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+// step `computer` at most `max_steps` times, catching panics. the computer stays
+// inspectable after a panic
+pub fn run_bounded<IN, OUT, OBS>(computer: &mut IntcodeComputer<IN, OUT, OBS>, max_steps: usize) -> Verdict
+where IN: Input, OUT: Output, OBS: Observer {
+    let run = || {
+        for _ in 0..max_steps {
+            match computer.step() {
+                ExecState::Running => continue,
+                state => return Verdict::Stopped(state),
+            }
+        }
+        Verdict::Timeout
+    };
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|payload| Verdict::Panic(panic_message(&*payload)))
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Candidate {
+    // (address, value) written into the program before it starts
+    pub patches: Vec<(usize, Word)>,
+    pub inputs: Vec<Word>,
+}
+
+pub struct Outcome {
+    pub index: usize,
+    pub candidate: Candidate,
+    // a candidate that loops or panics the interpreter fails on its own
+    pub verdict: Verdict,
+    computer: IntcodeComputer<BufferInput, BufferOutput>,
+}
+
+impl Outcome {
+    pub fn outputs(&self) -> &[Word] {
+        self.computer.output_ref().deref()
+    }
+
+    // final memory of the run
+    pub fn mem(&self, addr: usize) -> Word {
+        self.computer.peek(addr)
+    }
+}
+
+fn run_candidate(mem: &[Word], index: usize, candidate: &Candidate, options: &Options) -> Outcome {
+    let mut mem = mem.to_vec();
+    for (addr, val) in candidate.patches.iter() {
+        if mem.len() <= *addr {
+            mem.resize(addr + 1, 0);
+        }
+        mem[*addr] = *val;
+    }
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(&candidate.inputs), BufferOutput::default());
+    if let Some(profile) = options.profile {
+        computer.set_profile(profile);
+    }
+    let verdict = run_bounded(&mut computer, options.max_steps);
+    Outcome { index, candidate: candidate.clone(), verdict, computer }
+}
+
+// run candidates on all cores. `visit` sees every outcome and returns true to stop early;
+// candidates after a stopping one are skipped, earlier ones still run
+fn sweep<F>(mem: &[Word], candidates: &[Candidate], options: &Options, visit: F)
+where F: Fn(Outcome) -> bool + Sync {
+    let next = AtomicUsize::new(0);
+    let stop_at = AtomicUsize::new(usize::MAX);
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    thread::scope(|scope| {
+        for _ in 0..workers.min(candidates.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= candidates.len() || index > stop_at.load(Ordering::Relaxed) {
+                    break;
+                }
+                if visit(run_candidate(mem, index, &candidates[index], options)) {
+                    stop_at.fetch_min(index, Ordering::Relaxed);
+                }
+            });
+        }
+    });
+}
+
+// the matching candidate with the lowest index, same as a sequential search would find
+pub fn find_first<P>(mem: &[Word], candidates: &[Candidate], options: &Options, predicate: P) -> Option<Outcome>
+where P: Fn(&Outcome) -> bool + Sync {
+    let best: Mutex<Option<Outcome>> = Mutex::new(None);
+    sweep(mem, candidates, options, |outcome| {
+        if !predicate(&outcome) {
+            return false;
+        }
+        let mut best = best.lock().unwrap();
+        if best.as_ref().is_none_or(|b| outcome.index < b.index) {
+            *best = Some(outcome);
+        }
+        true
+    });
+    best.into_inner().unwrap()
+}
+
+// the candidate with the highest objective, the lowest index on ties
+pub fn maximize<F, K>(mem: &[Word], candidates: &[Candidate], options: &Options, objective: F) -> Option<Outcome>
+where F: Fn(&Outcome) -> K + Sync, K: Ord + Send {
+    let best: Mutex<Option<(K, Outcome)>> = Mutex::new(None);
+    sweep(mem, candidates, options, |outcome| {
+        let key = objective(&outcome);
+        let mut best = best.lock().unwrap();
+        let better = match best.as_ref() {
+            None => true,
+            Some((k, b)) => key > *k || (key == *k && outcome.index < b.index),
+        };
+        if better {
+            *best = Some((key, outcome));
+        }
+        false
+    });
+    best.into_inner().unwrap().map(|(_, outcome)| outcome)
+}
+
+// every combination of values for the given addresses, the last address changing fastest
+pub fn patch_grid(addrs: &[usize], values: &[Word]) -> Vec<Candidate> {
+    let mut result = vec![Candidate::default()];
+    for addr in addrs {
+        result = result.into_iter()
+            .flat_map(|c| values.iter().map(move |v| {
+                let mut c = c.clone();
+                c.patches.push((*addr, *v));
+                c
+            }))
+            .collect();
+    }
+    result
+}
+
+#[test]
+fn test_sweep() {
+    // output input[0] * input[1]
+    let prog = vec![3, 11, 3, 12, 2, 11, 12, 13, 4, 13, 99];
+    let candidates: Vec<Candidate> = (0..20)
+        .map(|i| Candidate { patches: vec![], inputs: vec![i, 20 - i] })
+        .collect();
+    let options = Options { max_steps: 100, ..Default::default() };
+    let best = maximize(&prog, &candidates, &options, |o| o.outputs()[0]).unwrap();
+    assert_eq!(best.candidate.inputs, vec![10, 10]);
+    let first = find_first(&prog, &candidates, &options, |o| o.outputs()[0] > 50).unwrap();
+    assert_eq!(first.index, 3);
+
+    // jumps to a loop, to a write in immediate mode, or to a halt
+    let prog = vec![1105, 1, 0, 1105, 1, 3, 11101, 0, 0, 0, 99];
+    let candidates: Vec<Candidate> = [3, 6, 10].iter()
+        .map(|v| Candidate { patches: vec![(2, *v)], inputs: vec![] })
+        .collect();
+    let verdicts: Mutex<Vec<(usize, Verdict)>> = Mutex::new(Vec::new());
+    let options = Options { max_steps: 1000, ..Default::default() };
+    let halted = find_first(&prog, &candidates, &options, |o| {
+        verdicts.lock().unwrap().push((o.index, o.verdict.clone()));
+        o.verdict == Verdict::Stopped(ExecState::Halted)
+    });
+    assert_eq!(halted.unwrap().index, 2);
+    let mut verdicts = verdicts.into_inner().unwrap();
+    verdicts.sort_by_key(|(i, _)| *i);
+    assert_eq!(verdicts[0].1, Verdict::Timeout);
+    assert!(matches!(verdicts[1].1, Verdict::Panic(_)));
+
+    let grid = patch_grid(&[1, 2], &[0, 1, 2]);
+    assert_eq!(grid.len(), 9);
+    assert_eq!(grid[5].patches, vec![(1, 1), (2, 2)]);
+}
@@ -0,0 +1,429 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use super::inst::{Operation, Word};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Symbol {
+    // initial value of a memory cell
+    Memory(usize),
+    // the k-th input, counting from 0
+    Input(usize),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Expr {
+    Const(Word),
+    Sym(Symbol),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    Lt(Rc<Expr>, Rc<Expr>),
+    Eq(Rc<Expr>, Rc<Expr>),
+    // memory at a symbolic address
+    Load(Rc<Expr>),
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Symbol::Memory(addr) => write!(f, "mem[{}]", addr),
+            Symbol::Input(k) => write!(f, "input[{}]", k),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(v) => write!(f, "{}", v),
+            Expr::Sym(s) => write!(f, "{}", s),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::Lt(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Eq(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Load(a) => write!(f, "*{}", a),
+        }
+    }
+}
+
+type E = Rc<Expr>;
+
+fn constant(v: Word) -> E {
+    Rc::new(Expr::Const(v))
+}
+
+// constructors fold constants, so fully concrete programs stay concrete. folding wraps
+// on overflow, like a release build of the interpreter
+fn binary(op: Operation, a: E, b: E) -> E {
+    use Expr::Const;
+    Rc::new(match (op, &*a, &*b) {
+        (Operation::Add, Const(x), Const(y)) => Const(x.wrapping_add(*y)),
+        (Operation::Add, Const(0), _) => return b,
+        (Operation::Add, _, Const(0)) => return a,
+        (Operation::Multiply, Const(x), Const(y)) => Const(x.wrapping_mul(*y)),
+        (Operation::Multiply, Const(0), _) | (Operation::Multiply, _, Const(0)) => Const(0),
+        (Operation::Multiply, Const(1), _) => return b,
+        (Operation::Multiply, _, Const(1)) => return a,
+        (Operation::LessThan, Const(x), Const(y)) => Const((x < y) as Word),
+        (Operation::Equals, Const(x), Const(y)) => Const((x == y) as Word),
+        (Operation::Equals, _, _) if a == b => Const(1),
+        (Operation::Add, _, _) => Expr::Add(a, b),
+        (Operation::Multiply, _, _) => Expr::Mul(a, b),
+        (Operation::LessThan, _, _) => Expr::Lt(a, b),
+        _ => Expr::Eq(a, b),
+    })
+}
+
+// `constant + sum(coeffs[s] * s)`
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Affine {
+    pub coeffs: BTreeMap<Symbol, Word>,
+    pub constant: Word,
+}
+
+impl Affine {
+    // None if the expression is not affine, or a coefficient overflows
+    pub fn of(expr: &Expr) -> Option<Affine> {
+        match expr {
+            Expr::Const(v) => Some(Affine { constant: *v, ..Default::default() }),
+            Expr::Sym(s) => Some(Affine { coeffs: BTreeMap::from([(*s, 1)]), constant: 0 }),
+            Expr::Add(a, b) => {
+                let (mut a, b) = (Affine::of(a)?, Affine::of(b)?);
+                for (s, c) in b.coeffs {
+                    let sum = a.coeffs.get(&s).copied().unwrap_or(0).checked_add(c)?;
+                    a.coeffs.insert(s, sum);
+                }
+                a.coeffs.retain(|_, c| *c != 0);
+                a.constant = a.constant.checked_add(b.constant)?;
+                Some(a)
+            },
+            Expr::Mul(a, b) => {
+                let (a, b) = (Affine::of(a)?, Affine::of(b)?);
+                let (factor, mut other) = match (a.coeffs.is_empty(), b.coeffs.is_empty()) {
+                    (true, _) => (a.constant, b),
+                    (_, true) => (b.constant, a),
+                    _ => return None,
+                };
+                for c in other.coeffs.values_mut() {
+                    *c = c.checked_mul(factor)?;
+                }
+                other.coeffs.retain(|_, c| *c != 0);
+                other.constant = other.constant.checked_mul(factor)?;
+                Some(other)
+            },
+            _ => None,
+        }
+    }
+
+    // None on overflow, where the interpreter would not compute the same value
+    pub fn eval(&self, values: &BTreeMap<Symbol, Word>) -> Option<Word> {
+        self.coeffs.iter().try_fold(self.constant, |sum, (s, c)| {
+            sum.checked_add(c.checked_mul(values.get(s).copied().unwrap_or(0))?)
+        })
+    }
+
+    // values within `ranges` for every symbol such that the expression equals `target`.
+    // all but the last symbol are enumerated, the last one is solved for directly. values
+    // for which the expression overflows are not solutions
+    pub fn solve(&self, target: Word, ranges: &BTreeMap<Symbol, Range<Word>>) -> Option<BTreeMap<Symbol, Word>> {
+        let symbols: Vec<Symbol> = self.coeffs.keys().copied().collect();
+        let (last, rest) = symbols.split_last()?;
+        let mut values = BTreeMap::new();
+        self.solve_from(target, ranges, rest, *last, &mut values).then_some(values)
+    }
+
+    fn solve_from(&self, target: Word, ranges: &BTreeMap<Symbol, Range<Word>>,
+                  rest: &[Symbol], last: Symbol, values: &mut BTreeMap<Symbol, Word>) -> bool {
+        let Some((sym, rest)) = rest.split_first() else {
+            values.insert(last, 0);
+            let Some(remaining) = self.eval(values).and_then(|v| target.checked_sub(v)) else { return false };
+            let coeff = self.coeffs[&last];
+            let (Some(val), Some(0)) = (remaining.checked_div(coeff), remaining.checked_rem(coeff)) else {
+                return false;
+            };
+            values.insert(last, val);
+            return ranges.get(&last).is_none_or(|r| r.contains(&val));
+        };
+        let range = ranges.get(sym).cloned().unwrap_or(0..1);
+        for val in range {
+            values.insert(*sym, val);
+            if self.solve_from(target, ranges, rest, last, values) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl fmt::Display for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (s, c) in self.coeffs.iter() {
+            write!(f, "{}*{} + ", c, s)?;
+        }
+        write!(f, "{}", self.constant)
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Constraint {
+    pub expr: E,
+    // whether the path requires `expr` to be non-zero or zero
+    pub nonzero: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PathEnd {
+    Halted,
+    WaitingInput,
+    StepLimit,
+    // the path needs something this executor cannot model
+    Unsupported { pc: usize, reason: &'static str },
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub end: PathEnd,
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<E>,
+    mem: BTreeMap<usize, E>,
+}
+
+impl Path {
+    pub fn mem(&self, addr: usize) -> E {
+        self.mem.get(&addr).cloned().unwrap_or_else(|| constant(0))
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    pc: usize,
+    relative_base: Word,
+    inputs: usize,
+    steps: usize,
+    // a symbolic load reads the initial memory, which is only right before the first store
+    stored: bool,
+    path: Path,
+}
+
+pub struct SymbolicExecutor {
+    pub max_steps: usize,
+    pub max_paths: usize,
+    // number of inputs that are symbols; reading beyond that ends the path
+    pub max_inputs: usize,
+}
+
+impl Default for SymbolicExecutor {
+    fn default() -> Self {
+        SymbolicExecutor { max_steps: 100_000, max_paths: 64, max_inputs: 16 }
+    }
+}
+
+enum Step {
+    Continue,
+    End(PathEnd),
+    // the condition of the branch at the pc is symbolic
+    Fork(E, usize),
+}
+
+impl SymbolicExecutor {
+    // execute `mem` with the cells in `symbols` replaced by symbols, forking on branches
+    // whose condition depends on a symbol
+    pub fn explore(&self, mem: &[Word], symbols: &[usize]) -> Vec<Path> {
+        let mut initial: BTreeMap<usize, E> = mem.iter().enumerate().map(|(i, v)| (i, constant(*v))).collect();
+        for addr in symbols {
+            initial.insert(*addr, Rc::new(Expr::Sym(Symbol::Memory(*addr))));
+        }
+        let path = Path { end: PathEnd::Halted, constraints: Vec::new(), outputs: Vec::new(), mem: initial };
+        let mut pending = vec![State { pc: 0, relative_base: 0, inputs: 0, steps: 0, stored: false, path }];
+        let mut finished = Vec::new();
+
+        while let Some(mut state) = pending.pop() {
+            let end = loop {
+                if state.steps >= self.max_steps {
+                    break PathEnd::StepLimit;
+                }
+                state.steps += 1;
+                match self.step(&mut state) {
+                    Step::Continue => (),
+                    Step::End(end) => break end,
+                    Step::Fork(cond, target) => {
+                        if finished.len() + pending.len() + 1 >= self.max_paths {
+                            break PathEnd::Unsupported { pc: state.pc, reason: "too many paths" };
+                        }
+                        let mut taken = state.clone();
+                        taken.pc = target;
+                        taken.path.constraints.push(Constraint { expr: cond.clone(), nonzero: true });
+                        pending.push(taken);
+                        state.pc += 3;
+                        state.path.constraints.push(Constraint { expr: cond, nonzero: false });
+                    },
+                }
+            };
+            state.path.end = end;
+            finished.push(state.path);
+        }
+        finished
+    }
+
+    fn step(&self, state: &mut State) -> Step {
+        let pc = state.pc;
+        let unsupported = |reason| Step::End(PathEnd::Unsupported { pc, reason });
+        let Expr::Const(inst) = *state.path.mem(pc) else { return unsupported("symbolic opcode") };
+        if !Operation::is_builtin_opcode(inst % 100) {
+            return unsupported("unknown opcode");
+        }
+        let op = Operation::from(inst % 100);
+        let mut operands: Vec<(i8, E)> = Vec::new();
+        for i in 0..op.instruction_len() - 1 {
+            let mode = ((inst / 100 / 10i64.pow(i as u32)) % 10) as i8;
+            operands.push((mode, state.path.mem(pc + 1 + i)));
+        }
+
+        let address = |state: &State, (mode, word): &(i8, E)| -> Result<usize, &'static str> {
+            let addr = match (mode, &**word) {
+                (0, Expr::Const(a)) => Some(*a),
+                (2, Expr::Const(a)) => state.relative_base.checked_add(*a),
+                (0 | 2, _) => return Err("symbolic address"),
+                _ => return Err("invalid parameter mode"),
+            };
+            match addr {
+                Some(addr) if addr >= 0 => Ok(addr as usize),
+                _ => Err("address out of range"),
+            }
+        };
+        let read = |state: &State, operand: &(i8, E)| -> Result<E, &'static str> {
+            let (mode, word) = operand;
+            match (mode, &**word) {
+                (1, _) => Ok(word.clone()),
+                (0 | 2, Expr::Const(_)) => address(state, operand).map(|addr| state.path.mem(addr)),
+                (0 | 2, _) if state.stored => Err("load may alias a store"),
+                (0, _) => Ok(Rc::new(Expr::Load(word.clone()))),
+                (2, _) => Ok(Rc::new(Expr::Load(binary(Operation::Add, constant(state.relative_base), word.clone())))),
+                _ => Err("invalid parameter mode"),
+            }
+        };
+        // the operands the instruction reads, in parameter order
+        let mut vals: Vec<E> = Vec::new();
+        for i in op.roles().0 {
+            match read(state, &operands[*i]) {
+                Ok(val) => vals.push(val),
+                Err(reason) => return unsupported(reason),
+            }
+        }
+
+        match op {
+            Operation::Add | Operation::Multiply | Operation::LessThan | Operation::Equals => {
+                let val = binary(op, vals[0].clone(), vals[1].clone());
+                match address(state, &operands[2]) {
+                    Ok(addr) => { state.path.mem.insert(addr, val); state.stored = true; },
+                    Err(reason) => return unsupported(reason),
+                }
+            },
+            Operation::Input => {
+                if state.inputs >= self.max_inputs {
+                    return Step::End(PathEnd::WaitingInput);
+                }
+                let val = Rc::new(Expr::Sym(Symbol::Input(state.inputs)));
+                state.inputs += 1;
+                match address(state, &operands[0]) {
+                    Ok(addr) => { state.path.mem.insert(addr, val); state.stored = true; },
+                    Err(reason) => return unsupported(reason),
+                }
+            },
+            Operation::Output => {
+                let val = vals[0].clone();
+                state.path.outputs.push(val);
+            },
+            Operation::JumpIfTrue | Operation::JumpIfFalse => {
+                let Expr::Const(target) = *vals[1] else { return unsupported("symbolic jump target") };
+                let mut cond = vals[0].clone();
+                if op == Operation::JumpIfFalse {
+                    cond = binary(Operation::Equals, cond, constant(0));
+                }
+                // decided by an earlier branch on the same condition
+                let known = state.path.constraints.iter().find(|c| c.expr == cond).map(|c| c.nonzero);
+                match (&*cond, known) {
+                    (Expr::Const(c), _) if *c != 0 => { state.pc = target as usize; return Step::Continue; },
+                    (Expr::Const(_), _) | (_, Some(false)) => (),
+                    (_, Some(true)) => { state.pc = target as usize; return Step::Continue; },
+                    (_, None) => return Step::Fork(cond, target as usize),
+                }
+            },
+            Operation::AdjustRelativeBase => {
+                let Expr::Const(delta) = *vals[0] else { return unsupported("symbolic relative base") };
+                let Some(base) = state.relative_base.checked_add(delta) else { return unsupported("relative base overflow") };
+                state.relative_base = base;
+            },
+            Operation::Halt => return Step::End(PathEnd::Halted),
+            Operation::Extension { .. } => return unsupported("extension opcode"),
+        }
+        state.pc += op.instruction_len();
+        Step::Continue
+    }
+}
+
+#[test]
+fn test_invert_day2() {
+    use super::program::Program;
+
+    let mem = Program::parse(include_str!("../../input/2")).unwrap().words;
+    let paths = SymbolicExecutor::default().explore(&mem, &[1, 2]);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].end, PathEnd::Halted);
+    let affine = Affine::of(&paths[0].mem(0)).unwrap();
+    let (noun, verb) = (Symbol::Memory(1), Symbol::Memory(2));
+    assert_eq!(affine.eval(&BTreeMap::from([(noun, 12), (verb, 2)])), Some(4138658));
+    let ranges = BTreeMap::from([(noun, 0..100), (verb, 0..100)]);
+    assert_eq!(affine.solve(19690720, &ranges), Some(BTreeMap::from([(noun, 72), (verb, 64)])));
+}
+
+#[test]
+fn test_path_constraints() {
+    // outputs 1 if the input is 8, otherwise 0
+    let prog = vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+    let paths = SymbolicExecutor::default().explore(&prog, &[]);
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].outputs[0].to_string(), "(input[0] == 8)");
+
+    // outputs 0 if the input is 0, otherwise 1, with a jump
+    let prog = vec![3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+    let paths = SymbolicExecutor::default().explore(&prog, &[]);
+    assert_eq!(paths.len(), 2);
+    for path in paths {
+        let output = if path.constraints[0].nonzero { 0 } else { 1 };
+        assert_eq!(*path.outputs[0], Expr::Const(output));
+    }
+
+    // a relative load through a symbolic address, before and after a store it may alias
+    let paths = SymbolicExecutor::default().explore(&[109, 5, 204, 0, 99], &[3]);
+    assert_eq!(paths[0].outputs[0].to_string(), "*(5 + mem[3])");
+    let prog = vec![109, 5, 3, 5, 204, 0, 99];
+    let paths = SymbolicExecutor::default().explore(&prog, &[]);
+    assert_eq!(paths[0].end, PathEnd::Unsupported { pc: 4, reason: "load may alias a store" });
+    let paths = SymbolicExecutor::default().explore(&[42, 99], &[]);
+    assert_eq!(paths[0].end, PathEnd::Unsupported { pc: 0, reason: "unknown opcode" });
+    let paths = SymbolicExecutor::default().explore(&[109, Word::MAX, 109, 1, 99], &[]);
+    assert_eq!(paths[0].end, PathEnd::Unsupported { pc: 2, reason: "relative base overflow" });
+    let paths = SymbolicExecutor::default().explore(&[204, -6, 99], &[]);
+    assert_eq!(paths[0].end, PathEnd::Unsupported { pc: 0, reason: "address out of range" });
+    let paths = SymbolicExecutor::default().explore(&[304, 0, 99], &[]);
+    assert_eq!(paths[0].end, PathEnd::Unsupported { pc: 0, reason: "invalid parameter mode" });
+    assert_eq!(*binary(Operation::Multiply, constant(Word::MAX), constant(2)), Expr::Const(-2));
+}
+
+#[test]
+fn test_affine_overflow() {
+    let input = Rc::new(Expr::Sym(Symbol::Input(0)));
+    let sum = Expr::Add(Rc::new(Expr::Add(input.clone(), constant(Word::MAX))), constant(1));
+    assert_eq!(Affine::of(&sum), None);
+    assert_eq!(Affine::of(&Expr::Mul(constant(Word::MAX), Rc::new(Expr::Mul(constant(2), input.clone())))), None);
+
+    let scaled = Affine::of(&Expr::Mul(constant(Word::MAX), input)).unwrap();
+    assert_eq!(scaled.eval(&BTreeMap::from([(Symbol::Input(0), 1)])), Some(Word::MAX));
+    assert_eq!(scaled.eval(&BTreeMap::from([(Symbol::Input(0), 2)])), None);
+    assert_eq!(scaled.solve(-2, &BTreeMap::new()), None);
+    assert_eq!(scaled.solve(Word::MAX, &BTreeMap::new()), Some(BTreeMap::from([(Symbol::Input(0), 1)])));
+}
@@ -0,0 +1,151 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::inst::{Instruction, Operation, Parameter, Word};
+use super::observer::Observer;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Source {
+    // the k-th value read from the input, counting from 0
+    Input(usize),
+    // the initial value at this address
+    Memory(usize),
+}
+
+pub type Labels = BTreeSet<Source>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Branch {
+    pub pc: usize,
+    pub taken: bool,
+    // sources of the condition and of the target
+    pub labels: Labels,
+}
+
+// observer that tracks which sources influence each value. labels flow through arithmetic,
+// comparisons and stores, and through the address of position and relative mode operands,
+// so a pointer read from a labeled cell taints the value it points to
+#[derive(Default, Debug)]
+pub struct TaintTracker {
+    shadow: BTreeMap<usize, Labels>,
+    relative_base: usize,
+    inputs: usize,
+    // operand labels of the executing instruction, computed at fetch
+    operands: Vec<Labels>,
+    current: Option<(usize, Operation)>,
+    pub outputs: Vec<(Word, Labels)>,
+    pub branches: Vec<Branch>,
+}
+
+impl TaintTracker {
+    // inputs are always labeled, initial memory only at `cells`
+    pub fn new(cells: &[usize]) -> Self {
+        TaintTracker {
+            shadow: cells.iter().map(|a| (*a, Labels::from([Source::Memory(*a)]))).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn labels(&self, addr: usize) -> Labels {
+        self.shadow.get(&addr).cloned().unwrap_or_default()
+    }
+
+    fn operand_labels(&self, param_addr: usize, param: Parameter) -> Labels {
+        let mut labels = self.labels(param_addr);
+        let addr = match param {
+            Parameter::AbsPosition(i) => Some(i),
+            Parameter::RelPosition(i) => Some((self.relative_base as i64 + i) as usize),
+            Parameter::Immediate(_) => None,
+        };
+        if let Some(addr) = addr {
+            labels.extend(self.labels(addr));
+        }
+        labels
+    }
+
+    fn union(&self, operands: &[Labels]) -> Labels {
+        operands.iter().flatten().copied().collect()
+    }
+}
+
+impl Observer for TaintTracker {
+    fn on_fetch(&mut self, pc: usize, inst: &Instruction) {
+        self.operands = inst.params.iter().enumerate()
+            .map(|(i, param)| self.operand_labels(pc + 1 + i, *param))
+            .collect();
+        self.current = Some((pc, inst.op));
+        if matches!(inst.op, Operation::JumpIfTrue | Operation::JumpIfFalse) {
+            // marked as taken by `on_jump`
+            self.branches.push(Branch { pc, taken: false, labels: self.union(&self.operands) });
+        }
+    }
+
+    fn on_write(&mut self, addr: usize, _val: Word) {
+        let labels = match self.current.as_ref().map(|(_, op)| op) {
+            Some(Operation::Input) => Labels::from([Source::Input(self.inputs - 1)]),
+            // every operand but the destination
+            Some(_) => self.union(&self.operands[..self.operands.len().saturating_sub(1)]),
+            None => Labels::new(),
+        };
+        if labels.is_empty() {
+            self.shadow.remove(&addr);
+        } else {
+            self.shadow.insert(addr, labels);
+        }
+    }
+
+    fn on_jump(&mut self, _from: usize, _to: usize) {
+        // only conditional jumps exist, so this is always the last recorded branch
+        if let Some(branch) = self.branches.last_mut() {
+            branch.taken = true;
+        }
+    }
+
+    fn on_relative_base(&mut self, _old: usize, new: usize) {
+        self.relative_base = new;
+    }
+
+    fn on_input(&mut self, _val: Word) {
+        self.inputs += 1;
+    }
+
+    fn on_output(&mut self, val: Word) {
+        self.outputs.push((val, self.union(&self.operands)));
+    }
+}
+
+#[test]
+fn test_day2_flow() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+    use super::program::Program;
+
+    let mut mem = Program::parse(include_str!("../../input/2")).unwrap().words;
+    mem[1] = 12;
+    mem[2] = 2;
+    let mut computer = IntcodeComputer::new(mem, BufferInput::new(&[]), BufferOutput::default())
+        .with_observer(TaintTracker::new(&[1, 2]));
+    computer.run_until_finish();
+    assert_eq!(computer.observer_ref().labels(0), Labels::from([Source::Memory(1), Source::Memory(2)]));
+
+    // a day 2 style sum at 0, then output
+    let prog = vec![1, 9, 10, 0, 4, 0, 99, 0, 0, 30, 40];
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[]), BufferOutput::default())
+        .with_observer(TaintTracker::new(&[9, 10]));
+    computer.run_until_finish();
+    assert_eq!(computer.observer_ref().outputs, vec![(70, Labels::from([Source::Memory(9), Source::Memory(10)]))]);
+}
+
+#[test]
+fn test_diagnostic_branches() {
+    use super::computer::IntcodeComputer;
+    use super::io::{BufferInput, BufferOutput};
+
+    // outputs 0 unless the input is 8, which jumps over the output to the halt
+    let prog = vec![3, 12, 1008, 12, 8, 13, 1005, 13, 11, 104, 0, 99, 0, 0];
+    let mut computer = IntcodeComputer::new(prog, BufferInput::new(&[8]), BufferOutput::default())
+        .with_observer(TaintTracker::new(&[]));
+    computer.run_until_finish();
+    let tracker = computer.observer_ref();
+    assert_eq!(tracker.branches, vec![Branch { pc: 6, taken: true, labels: Labels::from([Source::Input(0)]) }]);
+    assert!(tracker.outputs.is_empty());
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::computer::ExecState;
+use super::inst::Word;
+use super::isa::Fault;
+use super::scheduler::{RunResult, Scheduler};
+
+pub type NodeId = usize;
+
+struct Node {
+    name: String,
+    mem: Vec<Word>,
+    initial_inputs: Vec<Word>,
+    targets: Vec<NodeId>,
+    collect: bool,
+}
+
+// a graph of machines. an Intcode machine has a single input and a single output stream,
+// so nodes have no named ports: the input is fed by all incoming edges (fan-in) and the
+// output is copied to all outgoing edges (fan-out). nodes are addressed by name
+#[derive(Default)]
+pub struct Topology {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TopologyError {
+    // no machine can make progress but some are still waiting for input
+    Deadlock { waiting: Vec<String> },
+    // reported before a deadlock, since a faulted node usually causes it
+    Faulted { nodes: Vec<(String, Fault)> },
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyError::Deadlock { waiting } =>
+                write!(f, "deadlock, waiting for input: {}", waiting.join(", ")),
+            TopologyError::Faulted { nodes } => {
+                let nodes: Vec<String> = nodes.iter().map(|(name, fault)| format!("{}: {}", name, fault)).collect();
+                write!(f, "faulted: {}", nodes.join(", "))
+            },
+        }
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+#[derive(Debug)]
+pub struct NodeResult {
+    pub state: ExecState,
+    pub pc: usize,
+    // only recorded for nodes marked with `Topology::collect`
+    pub outputs: Vec<Word>,
+}
+
+#[derive(Debug)]
+pub struct TopologyResult {
+    nodes: BTreeMap<String, NodeResult>,
+}
+
+impl TopologyResult {
+    pub fn node(&self, name: &str) -> &NodeResult {
+        &self.nodes[name]
+    }
+
+    pub fn collected(&self, name: &str) -> &[Word] {
+        &self.nodes[name].outputs
+    }
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // nodes named `names[i]` running the same program, `names[i]` feeding `names[i+1]`
+    pub fn chain(names: &[&str], mem: &[Word]) -> Self {
+        let mut topology = Self::new();
+        for name in names {
+            topology.add_node(name, mem.to_vec());
+        }
+        for pair in names.windows(2) {
+            topology.connect(pair[0], pair[1]);
+        }
+        topology
+    }
+
+    // a chain whose last node feeds the first one
+    pub fn ring(names: &[&str], mem: &[Word]) -> Self {
+        let mut topology = Self::chain(names, mem);
+        if let (Some(first), Some(last)) = (names.first(), names.last()) {
+            topology.connect(last, first);
+        }
+        topology
+    }
+
+    pub fn add_node(&mut self, name: &str, mem: Vec<Word>) -> NodeId {
+        assert!(self.find(name).is_none(), "duplicated node {}", name);
+        self.nodes.push(Node {
+            name: name.to_string(),
+            mem,
+            initial_inputs: Vec::new(),
+            targets: Vec::new(),
+            collect: false,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    fn node_mut(&mut self, name: &str) -> &mut Node {
+        let id = self.find(name).unwrap_or_else(|| panic!("unknown node {}", name));
+        &mut self.nodes[id]
+    }
+
+    pub fn connect(&mut self, from: &str, to: &str) {
+        let to = self.find(to).unwrap_or_else(|| panic!("unknown node {}", to));
+        self.node_mut(from).targets.push(to);
+    }
+
+    // queued before anything else, e.g. phase settings
+    pub fn initial_input(&mut self, name: &str, vals: &[Word]) {
+        self.node_mut(name).initial_inputs.extend_from_slice(vals);
+    }
+
+    pub fn collect(&mut self, name: &str) {
+        self.node_mut(name).collect = true;
+    }
+
+    // run every machine on the current thread until all of them halt, see `Scheduler`
+    pub fn run(&self) -> Result<TopologyResult, TopologyError> {
+        let mut scheduler = Scheduler::new();
+        for node in self.nodes.iter() {
+            let id = scheduler.add(node.mem.clone());
+            node.initial_inputs.iter().for_each(|v| scheduler.push_input(id, *v));
+            if node.collect {
+                scheduler.collect(id);
+            }
+        }
+        for (id, node) in self.nodes.iter().enumerate() {
+            node.targets.iter().for_each(|target| scheduler.connect(id, *target));
+        }
+
+        let result = scheduler.run();
+        let faulted: Vec<(String, Fault)> = self.nodes.iter().enumerate()
+            .filter_map(|(id, node)| match scheduler.state(id) {
+                ExecState::Faulted(fault) => Some((node.name.clone(), fault)),
+                _ => None,
+            })
+            .collect();
+        if !faulted.is_empty() {
+            return Err(TopologyError::Faulted { nodes: faulted });
+        }
+        if let RunResult::Quiescent(report) = result {
+            return Err(TopologyError::Deadlock {
+                waiting: report.waiting.iter().map(|w| self.nodes[w.machine].name.clone()).collect(),
+            });
+        }
+        Ok(TopologyResult {
+            nodes: self.nodes.iter().enumerate()
+                .map(|(id, node)| (node.name.clone(), NodeResult {
+                    state: scheduler.state(id),
+                    pc: scheduler.computer(id).pc(),
+                    outputs: scheduler.collected(id).to_vec(),
+                }))
+                .collect(),
+        })
+    }
+}
+
+#[test]
+fn test_fan_in() {
+    // two sources output their input, the sink adds two inputs
+    let source = vec![3, 9, 4, 9, 99];
+    let sink = vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99];
+    let mut topology = Topology::new();
+    topology.add_node("a", source.clone());
+    topology.add_node("b", source);
+    topology.add_node("sum", sink);
+    topology.connect("a", "sum");
+    topology.connect("b", "sum");
+    topology.initial_input("a", &[20]);
+    topology.initial_input("b", &[22]);
+    topology.collect("sum");
+    let result = topology.run().unwrap();
+    assert_eq!(result.collected("sum"), &[42]);
+    assert_eq!(result.node("a").state, ExecState::Halted);
+
+    let topology = Topology::ring(&["x", "y"], &[3, 9, 4, 9, 99]);
+    assert_eq!(topology.run().unwrap_err(), TopologyError::Deadlock { waiting: vec!["x".into(), "y".into()] });
+
+    // "broken" uses the invalid parameter mode 3, the others finish
+    let mut topology = Topology::chain(&["a", "b"], &[3, 9, 4, 9, 99]);
+    topology.initial_input("a", &[1]);
+    topology.add_node("broken", vec![301, 0, 0, 0, 99]);
+    let err = topology.run().unwrap_err();
+    assert!(matches!(&err, TopologyError::Faulted { nodes } if nodes.len() == 1 && nodes[0].0 == "broken"), "{:?}", err);
+}